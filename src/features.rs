@@ -1,5 +1,15 @@
+pub mod afk_macro;
+pub mod afk_session;
 pub mod anti_afk;
+pub mod crash_recovery;
 pub mod empty_session;
 pub mod force_close;
+pub mod game_build;
 pub mod game_networking;
+pub mod game_tuning;
+pub mod game_watcher;
+pub mod hang_monitor;
 pub mod launch;
+pub mod launcher_cleanup;
+pub mod playtime;
+pub mod restart;
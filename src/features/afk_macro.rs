@@ -0,0 +1,355 @@
+use crate::util::input::{InputEvent, Key};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// upper bound on the amount of steps a macro may expand to once repeats are unrolled
+const MAX_STEPS: usize = 1000;
+/// how many repeats may be nested inside one another
+const MAX_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedMacro {
+    pub name: String,
+    pub source: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// press and release each key in turn
+    Send(Vec<Key>),
+    Down(Key),
+    Up(Key),
+    Wait(Duration),
+    Mouse {
+        dx: i32,
+        dy: i32,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Macro {
+    steps: Vec<Step>,
+}
+
+impl Macro {
+    /// parses the macro text format, which is one step per line:
+    ///
+    /// ```text
+    /// # comments start with a hash
+    /// send numpad4 numpad6
+    /// down w
+    /// wait 250
+    /// up w
+    /// mouse 10 -5
+    /// repeat 3
+    ///     send space
+    ///     wait 100
+    /// end
+    /// ```
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut lines = source
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or_default().trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let steps = parse_block(&mut lines, None, 0)?;
+        Ok(Self { steps })
+    }
+
+    /// a plain tap of the given keys, optionally holding them down for a while
+    pub fn key_tap(keys: &[Key], hold: Duration) -> Self {
+        let steps = if hold.is_zero() {
+            vec![Step::Send(keys.to_vec())]
+        } else {
+            keys.iter()
+                .copied()
+                .map(Step::Down)
+                .chain([Step::Wait(hold)])
+                .chain(keys.iter().copied().map(Step::Up))
+                .collect()
+        };
+        Self { steps }
+    }
+
+    /// every key this macro touches
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys = Vec::new();
+        for step in &self.steps {
+            let step_keys = match step {
+                Step::Send(send) => send.as_slice(),
+                Step::Down(key) | Step::Up(key) => std::slice::from_ref(key),
+                Step::Wait(_) | Step::Mouse { .. } => &[],
+            };
+            for key in step_keys {
+                if !keys.contains(key) {
+                    keys.push(*key);
+                }
+            }
+        }
+        keys
+    }
+}
+
+fn parse_block<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    opened_at: Option<usize>,
+    depth: usize,
+) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    while let Some((number, line)) = lines.next() {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_ascii_lowercase();
+        let args = words.collect::<Vec<_>>();
+        match (command.as_str(), args.as_slice()) {
+            ("end", []) if opened_at.is_some() => return Ok(steps),
+            ("repeat", [count]) => {
+                let count = parse_number::<usize>(count, number)?;
+                if count == 0 {
+                    bail!("line {number}: repeat needs a count of at least 1");
+                }
+                // checked before the body too, so a huge count with a tiny body is caught early
+                if count > MAX_STEPS {
+                    bail!("line {number}: repeat expands to more than {MAX_STEPS} steps");
+                }
+                if depth >= MAX_DEPTH {
+                    bail!("line {number}: repeats are nested more than {MAX_DEPTH} deep");
+                }
+                let block = parse_block(lines, Some(number), depth + 1)?;
+                if block.is_empty() {
+                    bail!("line {number}: repeat has nothing to repeat");
+                }
+                if block.len().saturating_mul(count) > MAX_STEPS {
+                    bail!("line {number}: repeat expands to more than {MAX_STEPS} steps");
+                }
+                for _ in 0..count {
+                    steps.extend(block.iter().cloned());
+                }
+            }
+            ("send", keys) if !keys.is_empty() => {
+                let keys = keys
+                    .iter()
+                    .map(|key| parse_key(key, number))
+                    .collect::<Result<_>>()?;
+                steps.push(Step::Send(keys));
+            }
+            ("down", [key]) => steps.push(Step::Down(parse_key(key, number)?)),
+            ("up", [key]) => steps.push(Step::Up(parse_key(key, number)?)),
+            ("wait", [ms]) => {
+                let ms = parse_number::<u64>(ms, number)?;
+                steps.push(Step::Wait(Duration::from_millis(ms)));
+            }
+            ("mouse", [dx, dy]) => steps.push(Step::Mouse {
+                dx: parse_number(dx, number)?,
+                dy: parse_number(dy, number)?,
+            }),
+            _ => bail!("line {number}: couldn't understand \"{line}\""),
+        }
+        if steps.len() > MAX_STEPS {
+            bail!("line {number}: macro is longer than {MAX_STEPS} steps");
+        }
+    }
+    match opened_at {
+        Some(number) => bail!("line {number}: repeat is missing its end"),
+        None => Ok(steps),
+    }
+}
+
+fn parse_key(key: &str, number: usize) -> Result<Key> {
+    key.parse()
+        .with_context(|| format!("line {number}: unknown key \"{key}\""))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, number: usize) -> Result<T> {
+    value
+        .parse()
+        .ok()
+        .with_context(|| format!("line {number}: \"{value}\" is not a valid number"))
+}
+
+/// steps through a macro over multiple frames, honouring its waits
+#[derive(Debug)]
+pub struct Player {
+    steps: Vec<Step>,
+    position: usize,
+    resume_at: Instant,
+    held: Vec<Key>,
+}
+
+impl Player {
+    pub fn new(r#macro: Macro) -> Self {
+        Self {
+            steps: r#macro.steps,
+            position: 0,
+            resume_at: Instant::now(),
+            held: Vec::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.steps.len()
+    }
+
+    /// returns the events which are due to be sent now
+    pub fn advance(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        while !self.is_finished() && Instant::now() >= self.resume_at {
+            match &self.steps[self.position] {
+                Step::Send(keys) => events.extend(
+                    keys.iter()
+                        .flat_map(|&key| [InputEvent::key_down(key), InputEvent::key_up(key)]),
+                ),
+                Step::Down(key) => {
+                    self.held.push(*key);
+                    events.push(InputEvent::key_down(*key));
+                }
+                Step::Up(key) => {
+                    self.held.retain(|held| held != key);
+                    events.push(InputEvent::key_up(*key));
+                }
+                Step::Wait(duration) => self.resume_at = Instant::now() + *duration,
+                &Step::Mouse { dx, dy } => events.push(InputEvent::MouseMove { dx, dy }),
+            }
+            self.position += 1;
+        }
+        if self.is_finished() {
+            events.extend(self.held.drain(..).map(InputEvent::key_up));
+        }
+        events
+    }
+
+    /// stops playback, returning the events needed to release anything still held down
+    pub fn abort(&mut self) -> Vec<InputEvent> {
+        self.position = self.steps.len();
+        self.held.drain(..).map(InputEvent::key_up).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::input::{InputBackend, RecordingInput};
+
+    /// plays a macro to the end the way anti afk does, returning everything it sent
+    fn play(source: &str) -> Vec<(Instant, InputEvent)> {
+        let mut player = Player::new(Macro::parse(source).unwrap());
+        let mut input = RecordingInput::default();
+        while !player.is_finished() {
+            input.send(&player.advance());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        input.recorded
+    }
+
+    fn events(source: &str) -> Vec<InputEvent> {
+        play(source).into_iter().map(|(_, event)| event).collect()
+    }
+
+    fn error(source: &str) -> String {
+        Macro::parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn nested_repeats_unroll() {
+        let source = "
+            repeat 2
+                send w
+                repeat 2 # inner
+                    mouse 1 -1
+                end
+            END
+        ";
+        let once = [
+            InputEvent::key_down(Key::W),
+            InputEvent::key_up(Key::W),
+            InputEvent::MouseMove { dx: 1, dy: -1 },
+            InputEvent::MouseMove { dx: 1, dy: -1 },
+        ];
+        assert_eq!(events(source), [once, once].concat());
+    }
+
+    #[test]
+    fn waits_between_steps() {
+        let recorded = play("down space\nwait 30\nmouse 10 -5\nup space");
+        let events = recorded.iter().map(|&(_, event)| event).collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                InputEvent::key_down(Key::Space),
+                InputEvent::MouseMove { dx: 10, dy: -5 },
+                InputEvent::key_up(Key::Space),
+            ]
+        );
+        assert!(recorded[1].0 - recorded[0].0 >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn held_keys_are_released() {
+        assert_eq!(
+            events("down lshift\nsend numpad4"),
+            [
+                InputEvent::key_down(Key::LShift),
+                InputEvent::key_down(Key::Numpad4),
+                InputEvent::key_up(Key::Numpad4),
+                InputEvent::key_up(Key::LShift),
+            ]
+        );
+        let mut player = Player::new(Macro::parse("down w\nwait 10000\nup w").unwrap());
+        assert_eq!(player.advance(), [InputEvent::key_down(Key::W)]);
+        assert!(player.advance().is_empty());
+        assert_eq!(player.abort(), [InputEvent::key_up(Key::W)]);
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("send w\nsend f13"), "line 2: unknown key \"f13\"");
+        assert_eq!(error("wait soon"), "line 1: \"soon\" is not a valid number");
+        assert_eq!(error("mouse 1"), "line 1: couldn't understand \"mouse 1\"");
+        assert_eq!(error("send w\nend"), "line 2: couldn't understand \"end\"");
+        assert_eq!(
+            error("repeat 2\nrepeat 3\nsend w\nend"),
+            "line 1: repeat is missing its end"
+        );
+        assert_eq!(
+            error("repeat 1001\nsend w\nend"),
+            "line 1: repeat expands to more than 1000 steps"
+        );
+        assert_eq!(
+            error("repeat 10\nrepeat 200\nsend w\nend\nend"),
+            "line 1: repeat expands to more than 1000 steps"
+        );
+    }
+
+    #[test]
+    fn pointless_repeats() {
+        assert_eq!(
+            error("repeat 0\nsend w\nend"),
+            "line 1: repeat needs a count of at least 1"
+        );
+        assert_eq!(
+            error("send w\nrepeat 3\n# nothing\nend"),
+            "line 2: repeat has nothing to repeat"
+        );
+        // this used to spin through every repeat of the empty body
+        assert_eq!(
+            error("repeat 4000000000\nend"),
+            "line 1: repeat expands to more than 1000 steps"
+        );
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth| {
+            let mut source = "repeat 1\n".repeat(depth);
+            source.push_str("send w\n");
+            source.push_str(&"end\n".repeat(depth));
+            source
+        };
+        assert_eq!(events(&nested(MAX_DEPTH)), events("send w"));
+        assert_eq!(
+            error(&nested(MAX_DEPTH + 1)),
+            "line 9: repeats are nested more than 8 deep"
+        );
+    }
+}
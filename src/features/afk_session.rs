@@ -0,0 +1,100 @@
+use crate::{
+    features::anti_afk::AntiAfk,
+    gui::settings::{AfkSessionSettings, EndAction, SessionLimit},
+    util::win,
+};
+use std::time::{Duration, Instant};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug)]
+struct Running {
+    started: Instant,
+    ends: Instant,
+    presses_at_start: u32,
+}
+
+#[derive(Debug)]
+pub struct Summary {
+    pub ran_for: Duration,
+    pub presses: u32,
+    /// the end action which was run, if the session ran out rather than being stopped
+    pub end_action: Option<EndAction>,
+}
+
+#[derive(Debug, Default)]
+pub struct AfkSession {
+    running: Option<Running>,
+    pub summary: Option<Summary>,
+}
+
+impl AfkSession {
+    pub const fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.running
+            .as_ref()
+            .map(|running| running.ends.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn start(&mut self, settings: &AfkSessionSettings, anti_afk: &mut AntiAfk) {
+        let now = Instant::now();
+        self.running = Some(Running {
+            started: now,
+            ends: now + session_length(settings),
+            presses_at_start: anti_afk.presses,
+        });
+        self.summary = None;
+        anti_afk.enabled = true;
+        win::keep_awake(true);
+    }
+
+    pub fn stop(&mut self, anti_afk: &AntiAfk) {
+        self.finish(anti_afk, None);
+    }
+
+    /// returns the end action to run once the session has run out
+    pub fn run_timers(&mut self, end_action: EndAction, anti_afk: &AntiAfk) -> Option<EndAction> {
+        let running = self.running.as_ref()?;
+        if Instant::now() < running.ends {
+            return None;
+        }
+        self.finish(anti_afk, Some(end_action));
+        Some(end_action)
+    }
+
+    fn finish(&mut self, anti_afk: &AntiAfk, end_action: Option<EndAction>) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        self.summary = Some(Summary {
+            ran_for: running.started.elapsed(),
+            presses: anti_afk.presses - running.presses_at_start,
+            end_action,
+        });
+        win::keep_awake(false);
+    }
+}
+
+fn session_length(settings: &AfkSessionSettings) -> Duration {
+    match settings.limit {
+        SessionLimit::Duration => Duration::from_secs(settings.duration_mins * 60),
+        SessionLimit::ClockTime => {
+            let (hour, minute, second) = win::local_time();
+            let now = Duration::from_secs(
+                u64::from(hour) * 3600 + u64::from(minute) * 60 + u64::from(second),
+            );
+            let until = Duration::from_secs(
+                u64::from(settings.until_hour) * 3600 + u64::from(settings.until_minute) * 60,
+            );
+            // a time that has already passed today means that time tomorrow
+            if until > now {
+                until - now
+            } else {
+                DAY - (now - until)
+            }
+        }
+    }
+}
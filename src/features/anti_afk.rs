@@ -1,17 +1,73 @@
-use crate::util::{self, consts::game::WINDOW_TITLE};
-use std::time::{Duration, Instant};
-use windows::Win32::UI::Input::KeyboardAndMouse::{
-    INPUT, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, KEYBDINPUT, KEYEVENTF_KEYUP, MAPVK_VK_TO_VSC,
-    MapVirtualKeyW, SendInput, VIRTUAL_KEY, VK_NUMPAD4, VK_NUMPAD6,
+use crate::{
+    features::afk_macro::{Macro, Player},
+    gui::settings::AntiAfkSettings,
+    util::{
+        consts::game::WINDOW_TITLE,
+        input::{InputBackend, InputEvent, Win32Input},
+    },
 };
+use anyhow::{Context, Result, bail};
+use std::{
+    collections::VecDeque,
+    hash::{BuildHasher, RandomState},
+    time::{Duration, Instant, SystemTime},
+};
+use strum::Display;
+
+/// how close to one of our own injections an input must be for it to count as ours
+const INJECTION_SLACK: Duration = Duration::from_millis(250);
+const HISTORY_LENGTH: usize = 50;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum Blocked {
+    #[strum(to_string = "disabled")]
+    Disabled,
+    #[strum(to_string = "macro still running")]
+    Running,
+    #[strum(to_string = "interval not elapsed")]
+    IntervalNotElapsed,
+    #[strum(to_string = "not idle")]
+    NotIdle,
+    #[strum(to_string = "GTA not focused")]
+    NotFocused,
+    #[strum(to_string = "cursor visible")]
+    CursorVisible,
+    #[strum(to_string = "key held")]
+    KeyHeld,
+    #[strum(to_string = "invalid macro")]
+    InvalidMacro,
+}
+
+impl Blocked {
+    /// whether this is an unexpected reason for a press that was due to be skipped
+    const fn is_skip(self) -> bool {
+        !matches!(
+            self,
+            Self::Disabled | Self::Running | Self::IntervalNotElapsed
+        )
+    }
+}
 
-const INTERVAL: Duration = Duration::from_secs(60);
-const PRESS_KEYS: [VIRTUAL_KEY; 2] = [VK_NUMPAD4, VK_NUMPAD6];
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activity {
+    Pressed,
+    Skipped(Blocked),
+}
 
 #[derive(Debug)]
 pub struct AntiAfk {
     pub enabled: bool,
     pub interval: Instant,
+    pub history: VecDeque<(SystemTime, Activity)>,
+    pub presses: u32,
+    next_delay: Duration,
+    /// whether it was enabled last time the timers ran, to notice it being turned on
+    was_enabled: bool,
+    forced: bool,
+    player: Option<Player>,
+    last_real_input: Instant,
+    last_injection: Option<Instant>,
+    backend: Box<dyn InputBackend>,
 }
 
 impl Default for AntiAfk {
@@ -19,44 +75,418 @@ impl Default for AntiAfk {
         Self {
             enabled: false,
             interval: Instant::now(),
+            history: VecDeque::new(),
+            presses: 0,
+            next_delay: Duration::from_secs(AntiAfkSettings::default().interval_secs),
+            was_enabled: false,
+            forced: false,
+            player: None,
+            last_real_input: Instant::now(),
+            last_injection: None,
+            backend: Box::new(Win32Input),
         }
     }
 }
 
 impl AntiAfk {
-    pub fn can_activate(&self) -> bool {
-        use util::win::{is_any_key_pressed, is_cursor_visible, is_window_focused};
-        is_window_focused(WINDOW_TITLE) && !is_any_key_pressed(&PRESS_KEYS) && !is_cursor_visible()
+    pub fn set_backend(&mut self, backend: Box<dyn InputBackend>) {
+        // don't leave anything held down on the backend we're replacing
+        if let Some(mut player) = self.player.take() {
+            self.send(&player.abort());
+        }
+        self.backend = backend;
+    }
+
+    pub fn is_game_focused(&self) -> bool {
+        self.backend.is_window_focused(WINDOW_TITLE)
+    }
+
+    pub fn can_activate(&self, settings: &AntiAfkSettings) -> Result<(), Blocked> {
+        if !self.enabled {
+            return Err(Blocked::Disabled);
+        }
+        if self.player.is_some() {
+            return Err(Blocked::Running);
+        }
+        if !self.forced {
+            if self.interval.elapsed() < self.next_delay {
+                return Err(Blocked::IntervalNotElapsed);
+            }
+            if self.idle_time() < Duration::from_secs(settings.idle_threshold_secs) {
+                return Err(Blocked::NotIdle);
+            }
+        }
+        if !self.is_game_focused() {
+            return Err(Blocked::NotFocused);
+        }
+        if self.backend.is_cursor_visible() {
+            return Err(Blocked::CursorVisible);
+        }
+        let r#macro = active_macro(settings).map_err(|_| Blocked::InvalidMacro)?;
+        if self.backend.is_any_key_pressed(&r#macro.keys()) {
+            return Err(Blocked::KeyHeld);
+        }
+        Ok(())
     }
 
-    pub fn should_activate(&self) -> bool {
-        self.enabled && self.interval.elapsed() >= INTERVAL
+    /// how long until the next press is due, ignoring focus and the like
+    pub fn next_press_in(&self, settings: &AntiAfkSettings) -> Duration {
+        if self.forced {
+            return Duration::ZERO;
+        }
+        let interval_left = self.next_delay.saturating_sub(self.interval.elapsed());
+        let idle_left =
+            Duration::from_secs(settings.idle_threshold_secs).saturating_sub(self.idle_time());
+        interval_left.max(idle_left)
+    }
+
+    /// picks the delay until the next press again, such as once the user's settings are loaded
+    pub fn reschedule(&mut self, settings: &AntiAfkSettings) {
+        self.next_delay = next_delay(settings);
+    }
+
+    /// press as soon as nothing else is in the way, without waiting for the interval or idle time
+    pub const fn press_now(&mut self) {
+        self.forced = true;
+    }
+
+    /// time since the last input which didn't come from us
+    pub fn idle_time(&self) -> Duration {
+        self.last_real_input.elapsed()
+    }
+
+    /// why a running macro has to stop before it sends anything else, if it does
+    fn interruption(&self) -> Option<Blocked> {
+        if !self.enabled {
+            Some(Blocked::Disabled)
+        } else if !self.is_game_focused() {
+            Some(Blocked::NotFocused)
+        } else if self.backend.is_cursor_visible() {
+            Some(Blocked::CursorVisible)
+        } else {
+            None
+        }
     }
 
-    pub fn activate(&mut self) {
-        send(&PRESS_KEYS);
+    /// stops the running macro, letting go of anything it's holding down
+    fn stop(&mut self, reason: Blocked) {
+        let Some(mut player) = self.player.take() else {
+            return;
+        };
+        log::info!("Stopped the anti AFK macro: {reason}");
+        let events = player.abort();
+        if !events.is_empty() {
+            self.send(&events);
+        }
+    }
+
+    fn activate(&mut self, settings: &AntiAfkSettings) {
+        let r#macro = match active_macro(settings) {
+            Ok(r#macro) => r#macro,
+            Err(why) => {
+                log::warn!("Couldn't run anti AFK macro: {why}");
+                return;
+            }
+        };
+        self.player = Some(Player::new(r#macro));
         self.interval = Instant::now();
+        self.next_delay = next_delay(settings);
+        self.forced = false;
+        self.presses += 1;
+        self.record(Activity::Pressed);
     }
-}
 
-fn send(vk_codes: &[VIRTUAL_KEY]) {
-    let mut inputs = Vec::new();
-    for &vk_code in vk_codes {
-        let scan_code = unsafe { MapVirtualKeyW(u32::from(vk_code.0), MAPVK_VK_TO_VSC) } as u16;
-        for event in [KEYBD_EVENT_FLAGS(0), KEYEVENTF_KEYUP] {
-            let mut input = INPUT {
-                r#type: INPUT_KEYBOARD,
-                ..Default::default()
-            };
-            input.Anonymous.ki = KEYBDINPUT {
-                wVk: vk_code,
-                wScan: scan_code,
-                dwFlags: event,
-                time: 0,
-                dwExtraInfo: 0,
+    /// keeps track of real input, plays any steps of the running macro which are due,
+    /// and starts the macro again whenever nothing is blocking it
+    pub fn run_timers(&mut self, settings: &AntiAfkSettings) {
+        // it can be turned on from all over the place, so this is where its delay is picked
+        if self.enabled && !self.was_enabled {
+            self.reschedule(settings);
+        }
+        self.was_enabled = self.enabled;
+        self.track_idle();
+        // a macro mustn't carry on typing into whatever is in front of the game now
+        let interruption = self.player.is_some().then(|| self.interruption()).flatten();
+        if let Some(reason) = interruption {
+            self.stop(reason);
+        }
+        match self.can_activate(settings) {
+            Ok(()) => self.activate(settings),
+            Err(reason) if reason.is_skip() => self.record(Activity::Skipped(reason)),
+            Err(_) => {}
+        }
+        let Some(player) = &mut self.player else {
+            return;
+        };
+        let events = player.advance();
+        if player.is_finished() {
+            self.player = None;
+        }
+        if !events.is_empty() {
+            self.send(&events);
+        }
+    }
+
+    /// adds to the history, skipping repeats of the same activity so skips don't flood it
+    fn record(&mut self, activity: Activity) {
+        if activity != Activity::Pressed
+            && self
+                .history
+                .back()
+                .is_some_and(|(_, last)| *last == activity)
+        {
+            return;
+        }
+        if self.history.len() >= HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back((SystemTime::now(), activity));
+    }
+
+    fn send(&mut self, events: &[InputEvent]) {
+        self.backend.send(events);
+        self.last_injection = Some(Instant::now());
+    }
+
+    /// the system's idle time is reset by our own injected input too,
+    /// so only take the last input into account if it wasn't near one of our injections
+    fn track_idle(&mut self) {
+        let Some(last_input) = Instant::now().checked_sub(self.backend.idle_time()) else {
+            return;
+        };
+        let injected = self.last_injection.is_some_and(|injection| {
+            let distance = if last_input > injection {
+                last_input - injection
+            } else {
+                injection - last_input
             };
-            inputs.push(input);
+            distance <= INJECTION_SLACK
+        });
+        if !injected && last_input > self.last_real_input {
+            self.last_real_input = last_input;
+        }
+    }
+}
+
+/// the selected macro, or a plain key tap of the configured keys if none is selected
+pub fn active_macro(settings: &AntiAfkSettings) -> Result<Macro> {
+    match &settings.active_macro {
+        Some(name) => {
+            let named = settings
+                .macros
+                .iter()
+                .find(|m| m.name == *name)
+                .with_context(|| format!("no macro named \"{name}\""))?;
+            Macro::parse(&named.source)
+        }
+        None if settings.keys.is_empty() => bail!("no keys are selected"),
+        None => Ok(Macro::key_tap(
+            &settings.keys,
+            Duration::from_millis(settings.hold_ms),
+        )),
+    }
+}
+
+/// picks the delay until the next press, randomly offset by up to the configured jitter
+/// in either direction so that the presses don't form a perfectly periodic pattern
+fn next_delay(settings: &AntiAfkSettings) -> Duration {
+    let interval = Duration::from_secs(settings.interval_secs);
+    let jitter_ms = settings.jitter_secs * 1000;
+    if jitter_ms == 0 {
+        return interval;
+    }
+    let offset = RandomState::new().hash_one(Instant::now()) % (jitter_ms * 2 + 1);
+    (interval + Duration::from_millis(offset)).saturating_sub(Duration::from_millis(jitter_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::input::{Key, RecordingInput};
+    use std::{cell::RefCell, rc::Rc};
+
+    /// an enabled anti afk playing into a focused fake, with no idle time reported yet
+    fn setup() -> (AntiAfk, Rc<RefCell<RecordingInput>>) {
+        let input = Rc::new(RefCell::new(RecordingInput {
+            focused: true,
+            idle: Duration::MAX,
+            ..Default::default()
+        }));
+        let mut anti_afk = AntiAfk {
+            enabled: true,
+            ..Default::default()
+        };
+        anti_afk.set_backend(Box::new(Rc::clone(&input)));
+        (anti_afk, input)
+    }
+
+    fn sent(input: &Rc<RefCell<RecordingInput>>) -> Vec<InputEvent> {
+        let input = input.borrow();
+        input.recorded.iter().map(|&(_, event)| event).collect()
+    }
+
+    #[test]
+    fn blocked_reasons() {
+        let mut settings = AntiAfkSettings::default();
+        let (mut anti_afk, input) = setup();
+        anti_afk.enabled = false;
+        assert_eq!(anti_afk.can_activate(&settings), Err(Blocked::Disabled));
+        anti_afk.enabled = true;
+        assert_eq!(
+            anti_afk.can_activate(&settings),
+            Err(Blocked::IntervalNotElapsed)
+        );
+        anti_afk.next_delay = Duration::ZERO;
+        assert_eq!(anti_afk.can_activate(&settings), Err(Blocked::NotIdle));
+        anti_afk.press_now();
+        input.borrow_mut().focused = false;
+        assert_eq!(anti_afk.can_activate(&settings), Err(Blocked::NotFocused));
+        input.borrow_mut().focused = true;
+        input.borrow_mut().cursor_visible = true;
+        assert_eq!(
+            anti_afk.can_activate(&settings),
+            Err(Blocked::CursorVisible)
+        );
+        input.borrow_mut().cursor_visible = false;
+        input.borrow_mut().pressed = vec![Key::Numpad6];
+        assert_eq!(anti_afk.can_activate(&settings), Err(Blocked::KeyHeld));
+        input.borrow_mut().pressed.clear();
+        settings.active_macro = Some("Missing".to_owned());
+        assert_eq!(anti_afk.can_activate(&settings), Err(Blocked::InvalidMacro));
+        settings.active_macro = None;
+        assert_eq!(anti_afk.can_activate(&settings), Ok(()));
+        settings.hold_ms = 10_000;
+        anti_afk.run_timers(&settings);
+        anti_afk.press_now();
+        assert_eq!(anti_afk.can_activate(&settings), Err(Blocked::Running));
+    }
+
+    #[test]
+    fn skips_are_recorded_once() {
+        let settings = AntiAfkSettings::default();
+        let (mut anti_afk, input) = setup();
+        input.borrow_mut().focused = false;
+        anti_afk.press_now();
+        anti_afk.run_timers(&settings);
+        anti_afk.run_timers(&settings);
+        let history = anti_afk.history.iter().map(|&(_, activity)| activity);
+        assert_eq!(
+            history.collect::<Vec<_>>(),
+            [Activity::Skipped(Blocked::NotFocused)]
+        );
+        assert!(sent(&input).is_empty());
+        assert_eq!(anti_afk.presses, 0);
+    }
+
+    #[test]
+    fn taps_each_key_in_turn() {
+        let settings = AntiAfkSettings::default();
+        let (mut anti_afk, input) = setup();
+        anti_afk.press_now();
+        anti_afk.run_timers(&settings);
+        let tap = [
+            InputEvent::key_down(Key::Numpad4),
+            InputEvent::key_up(Key::Numpad4),
+            InputEvent::key_down(Key::Numpad6),
+            InputEvent::key_up(Key::Numpad6),
+        ];
+        assert_eq!(sent(&input), tap);
+        assert_eq!(anti_afk.presses, 1);
+        assert_eq!(
+            anti_afk.history.back().map(|&(_, activity)| activity),
+            Some(Activity::Pressed)
+        );
+        // the interval starts over, so nothing more is sent until it's elapsed
+        anti_afk.run_timers(&settings);
+        assert_eq!(sent(&input), tap);
+    }
+
+    #[test]
+    fn holds_keys_down() {
+        let settings = AntiAfkSettings {
+            keys: vec![Key::W, Key::Space],
+            hold_ms: 30,
+            ..Default::default()
+        };
+        let (mut anti_afk, input) = setup();
+        anti_afk.press_now();
+        anti_afk.run_timers(&settings);
+        assert_eq!(
+            sent(&input),
+            [
+                InputEvent::key_down(Key::W),
+                InputEvent::key_down(Key::Space)
+            ]
+        );
+        let started = Instant::now();
+        while sent(&input).len() < 4 && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(1));
+            anti_afk.run_timers(&settings);
         }
+        assert_eq!(
+            sent(&input)[2..],
+            [InputEvent::key_up(Key::W), InputEvent::key_up(Key::Space)]
+        );
+        let recorded = &input.borrow().recorded;
+        assert!(recorded[2].0 - recorded[0].0 >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn stops_when_interrupted() {
+        let settings = AntiAfkSettings {
+            keys: vec![Key::W],
+            hold_ms: 10_000,
+            ..Default::default()
+        };
+        let (mut anti_afk, input) = setup();
+        anti_afk.press_now();
+        anti_afk.run_timers(&settings);
+        assert_eq!(sent(&input), [InputEvent::key_down(Key::W)]);
+        input.borrow_mut().focused = false;
+        anti_afk.run_timers(&settings);
+        assert_eq!(
+            sent(&input),
+            [InputEvent::key_down(Key::W), InputEvent::key_up(Key::W)]
+        );
+        assert!(anti_afk.player.is_none());
+
+        input.borrow_mut().focused = true;
+        anti_afk.press_now();
+        anti_afk.run_timers(&settings);
+        anti_afk.enabled = false;
+        anti_afk.run_timers(&settings);
+        assert_eq!(sent(&input)[2..], sent(&input)[..2]);
+        assert!(anti_afk.player.is_none());
+    }
+
+    #[test]
+    fn invalid_macros_dont_count() {
+        let settings = AntiAfkSettings {
+            active_macro: Some("Missing".to_owned()),
+            ..Default::default()
+        };
+        let (mut anti_afk, input) = setup();
+        anti_afk.activate(&settings);
+        assert!(anti_afk.player.is_none());
+        assert_eq!(anti_afk.presses, 0);
+        assert!(anti_afk.history.is_empty());
+        assert!(sent(&input).is_empty());
+    }
+
+    #[test]
+    fn own_input_isnt_activity() {
+        let settings = AntiAfkSettings::default();
+        let (mut anti_afk, input) = setup();
+        anti_afk.last_real_input = Instant::now() - Duration::from_secs(120);
+        anti_afk.press_now();
+        anti_afk.run_timers(&settings);
+        // the system saw input just now, but that was the press we just sent
+        input.borrow_mut().idle = Duration::ZERO;
+        anti_afk.run_timers(&settings);
+        assert!(anti_afk.idle_time() >= Duration::from_secs(120));
+        // input well away from any of ours was the user's
+        anti_afk.last_injection = Some(Instant::now() - INJECTION_SLACK * 4);
+        anti_afk.run_timers(&settings);
+        assert!(anti_afk.idle_time() < INJECTION_SLACK);
     }
-    unsafe { SendInput(&inputs, size_of::<INPUT>() as i32) };
 }
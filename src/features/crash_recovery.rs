@@ -0,0 +1,97 @@
+use crate::{
+    features::{
+        anti_afk::AntiAfk,
+        playtime::{ExitCause, Run},
+    },
+    gui::settings::CrashSettings,
+};
+use std::time::{Duration, Instant};
+
+/// gives the game's crash reporting and the launcher a moment before launching again
+const RELAUNCH_DELAY: Duration = Duration::from_secs(10);
+/// a run lasting this long counts as the game being stable again, resetting the retries
+const STABLE_RUN: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Default)]
+pub struct CrashRecovery {
+    retries: u32,
+    relaunch_at: Option<Instant>,
+    /// anti AFK was on when the game crashed, so it's turned back on once the game is focused
+    awaiting_focus: bool,
+    gave_up: bool,
+}
+
+impl CrashRecovery {
+    /// keeps count of crashes in a row, and schedules a relaunch if there are retries left
+    pub fn on_exit(&mut self, run: &Run, settings: &CrashSettings, anti_afk: &mut AntiAfk) {
+        if run.duration() >= STABLE_RUN {
+            self.retries = 0;
+            self.gave_up = false;
+        }
+        if run.exit != ExitCause::Crash || !settings.auto_relaunch {
+            return;
+        }
+        if self.retries >= settings.max_retries {
+            log::error!(
+                "The game crashed {} times in a row, not relaunching it",
+                self.retries + 1
+            );
+            self.gave_up = true;
+            return;
+        }
+        self.retries += 1;
+        self.relaunch_at = Some(Instant::now() + RELAUNCH_DELAY);
+        if settings.reenable_anti_afk && anti_afk.enabled {
+            anti_afk.enabled = false;
+            self.awaiting_focus = true;
+        }
+        log::info!(
+            "Relaunching the game after a crash, attempt {} of {}",
+            self.retries,
+            settings.max_retries
+        );
+    }
+
+    /// forgets about any crashes, such as when the user launches the game themselves
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub const fn is_relaunch_pending(&self) -> bool {
+        self.relaunch_at.is_some()
+    }
+
+    pub fn status(&self, settings: &CrashSettings) -> Option<String> {
+        if self.gave_up {
+            Some(format!(
+                "The game kept crashing, gave up after {} relaunches.",
+                self.retries
+            ))
+        } else if self.relaunch_at.is_some() {
+            Some(format!(
+                "The game crashed, relaunching ({} of {})...",
+                self.retries, settings.max_retries
+            ))
+        } else if self.awaiting_focus {
+            Some("Relaunched, anti AFK resumes once the game is focused.".to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// returns true once it's time to relaunch the game
+    pub fn run_timers(&mut self, anti_afk: &mut AntiAfk) -> bool {
+        if self.awaiting_focus && self.relaunch_at.is_none() && anti_afk.is_game_focused() {
+            log::info!("Game focused again after relaunching, enabling anti AFK");
+            anti_afk.enabled = true;
+            self.awaiting_focus = false;
+        }
+        match self.relaunch_at {
+            Some(relaunch_at) if Instant::now() >= relaunch_at => {
+                self.relaunch_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
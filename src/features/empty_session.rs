@@ -1,7 +1,9 @@
-use crate::util::{
-    countdown::Countdown,
-    firewall::{Firewall, RuleDirection, RuleMode, RuleProtocol},
-    system_info::SystemInfo,
+use crate::{
+    features::game_watcher::GameWatcher,
+    util::{
+        countdown::Countdown,
+        firewall::{Firewall, RuleDirection, RuleMode, RuleProtocol},
+    },
 };
 use anyhow::Result;
 use std::time::{Duration, Instant};
@@ -44,8 +46,8 @@ impl EmptySession {
     }
 }
 
-pub fn activate(system_info: &mut SystemInfo, firewall: &Firewall) -> Result<bool> {
-    let Some(exe_path) = system_info.get_game_exe_path() else {
+pub fn activate(game_watcher: &GameWatcher, firewall: &Firewall) -> Result<bool> {
+    let Some(exe_path) = game_watcher.exe_path() else {
         log::info!("wasn't able to find game exe");
         return Ok(false);
     };
@@ -1,68 +1,152 @@
-use crate::util::{
-    consts::game::{EXE_ENHANCED, EXE_LEGACY},
-    system_info::SystemInfo,
+use crate::{
+    gui::settings::{ForceCloseSettings, ForceCloseTargets},
+    util::{
+        consts::game::LAUNCHER_EXES,
+        system_info::{KillError, KillMethod, Process, SystemInfo},
+    },
+};
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::Duration,
 };
-use std::time::{Duration, Instant};
 
-const INTERVAL: Duration = Duration::from_secs(3);
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
 
-#[derive(Debug)]
-pub struct ForceClose {
-    pub button_text: String,
-    timer: Instant,
-    counting: bool,
-    current_frame: bool,
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub name: String,
+    pub pid: u32,
+    pub result: Result<KillMethod, KillError>,
 }
 
-impl Default for ForceClose {
-    fn default() -> Self {
-        Self {
-            button_text: "Force close game".to_owned(),
-            timer: Instant::now(),
-            counting: false,
-            current_frame: false,
-        }
-    }
+#[derive(Debug, Default)]
+pub struct ForceClose {
+    pending: Option<Receiver<Outcome>>,
+    /// what the running force close is closing, before any outcomes come back
+    pending_pids: Vec<u32>,
+    pub outcomes: Vec<Outcome>,
 }
 
 impl ForceClose {
-    pub fn prompt(&mut self, force_close_button_clicked: bool, system_info: &mut SystemInfo) {
-        if force_close_button_clicked && !self.counting {
-            self.button_text = "Are you sure?".to_owned();
-            self.timer = Instant::now();
-            self.counting = true;
-            self.current_frame = true;
-        }
-        if self.counting && self.timer.elapsed() >= INTERVAL {
-            self.reset();
-        } else if force_close_button_clicked && !self.current_frame {
-            activate(system_info);
-            self.reset();
-        }
-        self.finish_current_frame();
+    pub const fn is_running(&self) -> bool {
+        self.pending.is_some()
     }
 
-    fn reset(&mut self) {
-        *self = Self::default();
+    /// whether the process is being closed right now, or was closed by the last force close
+    pub fn has_closed(&self, pid: u32) -> bool {
+        (self.is_running() && self.pending_pids.contains(&pid))
+            || self
+                .outcomes
+                .iter()
+                .any(|outcome| outcome.pid == pid && outcome.result.is_ok())
     }
 
-    const fn finish_current_frame(&mut self) {
-        if self.current_frame {
-            self.current_frame = false;
-        }
+    /// kills every target on a background thread, so that waiting for them doesn't block the ui.
+    /// starts from the game process the watcher already has, only rescanning for anything more
+    pub fn activate(
+        &mut self,
+        system_info: &mut SystemInfo,
+        settings: &ForceCloseSettings,
+        rockstar_dirs: &[PathBuf],
+        game: Option<&Process>,
+    ) {
+        let targets = match game {
+            Some(game)
+                if settings.targets == ForceCloseTargets::GameOnly && !settings.kill_children =>
+            {
+                vec![game.clone()]
+            }
+            _ => {
+                system_info.refresh();
+                targets(system_info, settings, rockstar_dirs, game)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }
+        };
+        self.close(targets, settings.graceful_first);
     }
-}
 
-fn activate(system_info: &mut SystemInfo) {
-    system_info.refresh();
-    system_info
-        .processes()
-        .iter()
-        .filter(|p| p.name() == EXE_ENHANCED || p.name() == EXE_LEGACY)
-        .for_each(|p| {
-            if !p.kill() {
-                log::error!("failed to force close game, probably due to access denied");
+    /// kills the given processes the same way, for when they aren't the usual targets
+    pub fn close(&mut self, targets: Vec<Process>, graceful_first: bool) {
+        let methods = if graceful_first {
+            vec![KillMethod::Graceful, KillMethod::Terminate]
+        } else {
+            vec![KillMethod::Terminate]
+        };
+        self.pending_pids = targets.iter().map(Process::pid).collect();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for process in targets {
+                let outcome = Outcome {
+                    name: process.name().to_string_lossy().into_owned(),
+                    pid: process.pid(),
+                    result: process.kill(&methods, KILL_TIMEOUT),
+                };
+                if sender.send(outcome).is_err() {
+                    break;
+                }
             }
         });
-    system_info.refresh();
+        self.outcomes.clear();
+        self.pending = Some(receiver);
+    }
+
+    /// collects the outcomes of a running force close
+    pub fn run_timers(&mut self) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        loop {
+            match pending.try_recv() {
+                Ok(outcome) => {
+                    match &outcome.result {
+                        Ok(method) => log::info!("{} {}: {method}", outcome.name, outcome.pid),
+                        Err(why) => log::error!(
+                            "failed to force close {} {}: {why}",
+                            outcome.name,
+                            outcome.pid
+                        ),
+                    }
+                    self.outcomes.push(outcome);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// every running process which force closing would kill, as of the last refresh
+pub fn targets<'a>(
+    system_info: &'a SystemInfo,
+    settings: &ForceCloseSettings,
+    rockstar_dirs: &[PathBuf],
+    game: Option<&'a Process>,
+) -> Vec<&'a Process> {
+    let found = game
+        .into_iter()
+        .chain(
+            system_info
+                .find_by_names(&settings.targets.exes(), false)
+                .into_iter()
+                .filter(|process| game.is_none_or(|game| game.pid() != process.pid())),
+        )
+        // plenty of other programs have a Launcher.exe, so only rockstar's own are fair game
+        .filter(|process| {
+            !LAUNCHER_EXES
+                .iter()
+                .any(|name| process.name().eq_ignore_ascii_case(name))
+                || process.is_inside(rockstar_dirs)
+        })
+        .collect();
+    if settings.kill_children {
+        system_info.with_children(found)
+    } else {
+        found
+    }
 }
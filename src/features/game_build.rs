@@ -0,0 +1,83 @@
+use crate::{
+    gui::settings::LaunchVersion,
+    util::pe_version::{self, VersionInfo},
+};
+use std::{collections::BTreeMap, path::Path};
+
+/// which build of the game is running, and whether it's changed since we last saw it
+#[derive(Debug, Default)]
+pub struct GameBuild {
+    pub running: Option<VersionInfo>,
+    /// the last build seen of each version, kept in the persistent state so that updates are
+    /// noticed across restarts of ours. switching versions isn't an update, so each is only
+    /// compared against itself
+    pub last_seen: BTreeMap<LaunchVersion, String>,
+    /// the build seen before the running one, until the update has been acknowledged
+    pub updated_from: Option<String>,
+}
+
+impl GameBuild {
+    pub fn on_started(&mut self, version: LaunchVersion, exe: Option<&Path>) {
+        let Some(exe) = exe else {
+            log::info!("Couldn't find the game's exe to read its version");
+            return;
+        };
+        let info = match pe_version::read(exe) {
+            Ok(info) => info,
+            Err(why) => {
+                log::warn!("Couldn't read the game's version: {why}");
+                return;
+            }
+        };
+        let build = info.to_string();
+        log::info!("Game build is {build} ({version})");
+        if let Some(last_seen) = self.last_seen.get(&version).filter(|&last| *last != build) {
+            log::warn!("The game ({version}) was updated from {last_seen} to {build}");
+            self.updated_from = Some(last_seen.clone());
+        }
+        self.last_seen.insert(version, build);
+        self.running = Some(info);
+    }
+
+    pub fn on_exited(&mut self) {
+        self.running = None;
+    }
+
+    pub fn dismiss(&mut self) {
+        self.updated_from = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/pe")
+            .join(name)
+    }
+
+    #[test]
+    fn versions_are_tracked_apart() {
+        let mut game_build = GameBuild::default();
+        game_build.on_started(LaunchVersion::Enhanced, Some(&fixture("version32.exe")));
+        game_build.on_started(LaunchVersion::Legacy, Some(&fixture("fixed_only64.exe")));
+        game_build.on_started(LaunchVersion::Enhanced, Some(&fixture("version32.exe")));
+        assert_eq!(game_build.updated_from, None);
+        assert_eq!(
+            game_build.last_seen,
+            BTreeMap::from([
+                (LaunchVersion::Enhanced, "1.2.3.4".to_owned()),
+                (LaunchVersion::Legacy, "5.6.7.8".to_owned()),
+            ])
+        );
+
+        game_build.on_started(LaunchVersion::Enhanced, Some(&fixture("fixed_only64.exe")));
+        assert_eq!(game_build.updated_from.as_deref(), Some("1.2.3.4"));
+        game_build.dismiss();
+        game_build.on_started(LaunchVersion::Legacy, Some(&fixture("fixed_only64.exe")));
+        assert_eq!(game_build.updated_from, None);
+    }
+}
@@ -1,11 +1,10 @@
 use crate::{
+    features::game_watcher::GameWatcher,
     gui::settings::BlockMethod,
-    util::{
-        firewall::{Firewall, RuleDirection, RuleMode, RuleProtocol},
-        system_info::SystemInfo,
-    },
+    util::firewall::{Firewall, RuleDirection, RuleMode, RuleProtocol},
 };
 use anyhow::Result;
+use std::path::PathBuf;
 use strum::{Display, EnumIter};
 
 const FILTER_NAME_EXE: &str = "[GTA Tools] Block outbound traffic for all of GTA V";
@@ -40,15 +39,20 @@ impl Default for GameNetworking {
 }
 
 impl GameNetworking {
-    pub fn block_exe(&mut self, system_info: &mut SystemInfo, firewall: &Firewall) -> Result<()> {
-        let Some(exe_path) = system_info.get_game_exe_path() else {
+    pub fn block_exe(&mut self, game_watcher: &GameWatcher, firewall: &Firewall) -> Result<()> {
+        let Some(exe_path) = game_watcher.exe_path() else {
             log::warn!("Unable to find game executable path.");
             return Ok(());
         };
+        self.block_exe_path(exe_path.to_path_buf(), firewall)
+    }
+
+    /// blocks an exe by its path, so that it works even when the game isn't running yet
+    pub fn block_exe_path(&mut self, exe_path: PathBuf, firewall: &Firewall) -> Result<()> {
         firewall
             .add(
                 FILTER_NAME_EXE,
-                RuleMode::Executable(exe_path.to_path_buf()),
+                RuleMode::Executable(exe_path),
                 RuleDirection::Out,
                 RuleProtocol::Any,
             )
@@ -78,6 +82,14 @@ impl GameNetworking {
             .inspect(|_| self.blocked = BlockedStatus::Unblocked)
     }
 
+    /// removes every rule we might have added, whatever the current block method
+    pub fn remove_all_rules(&mut self, firewall: &Firewall) -> Result<()> {
+        let exe = firewall.remove(FILTER_NAME_EXE);
+        let save_server = firewall.remove(FILTER_NAME_SAVE_SERVER);
+        exe.and(save_server)
+            .inspect(|_| self.blocked = BlockedStatus::Unblocked)
+    }
+
     pub fn ensure_block_exclusivity(
         &mut self,
         block_method: BlockMethod,
@@ -0,0 +1,148 @@
+use crate::{
+    gui::settings::{Priority, TuningSettings},
+    util::process_control::{Change, ProcessControl, Win32ProcessControl},
+};
+
+/// sets the game's priority and cpu affinity, for when it shares the pc with something heavy
+#[derive(Debug)]
+pub struct GameTuning {
+    control: Box<dyn ProcessControl>,
+    status: Option<String>,
+}
+
+impl Default for GameTuning {
+    fn default() -> Self {
+        Self {
+            control: Box::new(Win32ProcessControl),
+            status: None,
+        }
+    }
+}
+
+impl GameTuning {
+    pub fn set_control(&mut self, control: Box<dyn ProcessControl>) {
+        self.control = control;
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// makes every change the settings ask for, carrying on past any that fail
+    pub fn apply(&mut self, pid: u32, settings: &TuningSettings, cpus: usize) {
+        let changes = changes(settings, cpus);
+        if changes.is_empty() {
+            self.status = None;
+            return;
+        }
+        let mut failures = Vec::new();
+        for change in changes {
+            if let Err(why) = self.control.apply(pid, change) {
+                log::error!("Couldn't apply {change:?} to the game: {why:#}");
+                failures.push(format!("{why:#}"));
+            }
+        }
+        self.status = Some(if failures.is_empty() {
+            log::info!("Applied priority and affinity to the game (pid {pid})");
+            "Applied priority and affinity.".to_owned()
+        } else {
+            format!("Couldn't apply everything: {}.", failures.join(", "))
+        });
+    }
+}
+
+/// what the settings ask for, leaving out anything they leave alone
+pub fn changes(settings: &TuningSettings, cpus: usize) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if settings.priority != Priority::Unchanged {
+        changes.push(Change::Priority(settings.priority));
+    }
+    if let Some(mask) = affinity_mask(settings.affinity, cpus) {
+        changes.push(Change::Affinity(mask));
+    }
+    changes
+}
+
+/// the configured mask limited to the cpus this pc has, or None if it'd leave no cpus at all
+fn affinity_mask(affinity: u64, cpus: usize) -> Option<usize> {
+    let all = match cpus {
+        0 => return None,
+        1..64 => (1u64 << cpus) - 1,
+        _ => u64::MAX,
+    };
+    let mask = usize::try_from(affinity & all).ok()?;
+    (mask != 0).then_some(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::process_control::RecordingProcessControl;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn settings(priority: Priority, affinity: u64) -> TuningSettings {
+        TuningSettings {
+            auto_apply: true,
+            priority,
+            affinity,
+        }
+    }
+
+    #[test]
+    fn nothing_to_change() {
+        assert_eq!(changes(&settings(Priority::Unchanged, 0), 8), []);
+        let control = Rc::new(RefCell::new(RecordingProcessControl::default()));
+        let mut tuning = GameTuning::default();
+        tuning.set_control(Box::new(Rc::clone(&control)));
+        tuning.apply(1234, &settings(Priority::Unchanged, 0), 8);
+        assert!(control.borrow().recorded.is_empty());
+        assert_eq!(tuning.status(), None);
+    }
+
+    #[test]
+    fn mask_is_clipped_to_the_cpus() {
+        assert_eq!(affinity_mask(0b1111_0000, 6), Some(0b11_0000));
+        assert_eq!(affinity_mask(0b1010, 64), Some(0b1010));
+        assert_eq!(affinity_mask(u64::MAX, 128), usize::try_from(u64::MAX).ok());
+        assert_eq!(
+            changes(&settings(Priority::High, 0b1_0110), 4),
+            [Change::Priority(Priority::High), Change::Affinity(0b0110)]
+        );
+    }
+
+    #[test]
+    fn no_cpus_left() {
+        assert_eq!(affinity_mask(0b1_0000, 4), None);
+        assert_eq!(affinity_mask(0b1, 0), None);
+        assert_eq!(
+            changes(&settings(Priority::Idle, 0b1_0000), 4),
+            [Change::Priority(Priority::Idle)]
+        );
+    }
+
+    #[test]
+    fn carries_on_past_failures() {
+        let control = Rc::new(RefCell::new(RecordingProcessControl {
+            fail: vec![Change::Priority(Priority::AboveNormal)],
+            ..Default::default()
+        }));
+        let mut tuning = GameTuning::default();
+        tuning.set_control(Box::new(Rc::clone(&control)));
+        tuning.apply(1234, &settings(Priority::AboveNormal, 0b11), 8);
+        assert_eq!(
+            control.borrow().recorded,
+            [
+                (1234, Change::Priority(Priority::AboveNormal)),
+                (1234, Change::Affinity(0b11)),
+            ]
+        );
+        assert_eq!(
+            tuning.status(),
+            Some("Couldn't apply everything: access denied.")
+        );
+
+        tuning.set_control(Box::new(RecordingProcessControl::default()));
+        tuning.apply(1234, &settings(Priority::AboveNormal, 0b11), 8);
+        assert_eq!(tuning.status(), Some("Applied priority and affinity."));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    features::{
+        force_close::ForceClose,
+        playtime::{ExitCause, Run},
+    },
+    gui::settings::LaunchVersion,
+    util::{
+        consts::game::EXE_WER,
+        system_info::{Process, ProcessHandle, SystemInfo},
+    },
+};
+use std::{
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+use strum::IntoEnumIterator;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// how often to look for error reporting, which needs a whole new process list
+const ERROR_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// exit codes from this one up are NTSTATUS errors, such as access violations
+const NTSTATUS_ERROR: u32 = 0xC000_0000;
+
+#[derive(Debug)]
+pub enum GameEvent {
+    Started(LaunchVersion),
+    /// windows error reporting popped up for the game, which is still running behind it
+    ErrorReported,
+    Exited(Run),
+}
+
+#[derive(Debug)]
+struct Watched {
+    /// kept from when the game was found, so its exe path is only ever looked up once
+    process: Process,
+    /// can't always be opened, in which case we only notice the exit by the process vanishing
+    handle: Option<ProcessHandle>,
+    version: LaunchVersion,
+    started: SystemTime,
+    anti_afk: Duration,
+    error_reported: bool,
+}
+
+impl Watched {
+    fn into_run(self, exit: ExitCause) -> Run {
+        Run {
+            started: self.started,
+            ended: SystemTime::now(),
+            version: self.version,
+            exit,
+            anti_afk: self.anti_afk,
+        }
+    }
+}
+
+/// keeps an eye on the game's process, noticing when it starts and how it exits,
+/// so that the rest of the app can ask it about the game instead of searching for it again
+#[derive(Debug)]
+pub struct GameWatcher {
+    watched: Option<Watched>,
+    last_poll: Instant,
+    last_error_poll: Instant,
+    last_tick: Instant,
+}
+
+impl Default for GameWatcher {
+    fn default() -> Self {
+        Self {
+            watched: None,
+            // look for the game straight away, as other features rely on us having found it
+            last_poll: Instant::now()
+                .checked_sub(POLL_INTERVAL)
+                .unwrap_or_else(Instant::now),
+            last_error_poll: Instant::now(),
+            last_tick: Instant::now(),
+        }
+    }
+}
+
+impl GameWatcher {
+    pub fn is_game_running(&self) -> bool {
+        self.watched.is_some()
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.watched.as_ref().map(|watched| watched.process.pid())
+    }
+
+    /// the game's process as it was when it was found, so it doesn't need finding again
+    pub fn process(&self) -> Option<&Process> {
+        self.watched.as_ref().map(|watched| &watched.process)
+    }
+
+    pub fn exe_path(&self) -> Option<&Path> {
+        self.watched.as_ref()?.process.exe()
+    }
+
+    /// the current run's start time and version, if the game is running
+    pub fn current(&self) -> Option<(SystemTime, LaunchVersion)> {
+        self.watched
+            .as_ref()
+            .map(|watched| (watched.started, watched.version))
+    }
+
+    pub fn run_timers(
+        &mut self,
+        system_info: &mut SystemInfo,
+        force_close: &ForceClose,
+        anti_afk_enabled: bool,
+    ) -> Option<GameEvent> {
+        let tick = self.last_tick.elapsed();
+        self.last_tick = Instant::now();
+        if let (Some(watched), true) = (&mut self.watched, anti_afk_enabled) {
+            watched.anti_afk += tick;
+        }
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+        let Some(watched) = &mut self.watched else {
+            system_info.refresh();
+            return self.find_game(system_info);
+        };
+        // the handle tells us when it exits, so the process list is only needed now and then
+        let rescan =
+            watched.handle.is_none() || self.last_error_poll.elapsed() >= ERROR_POLL_INTERVAL;
+        if rescan {
+            system_info.refresh();
+            self.last_error_poll = Instant::now();
+        }
+        if rescan && !watched.error_reported && is_error_reported(system_info, &watched.process) {
+            log::error!("Windows error reporting appeared for the game, it probably crashed");
+            watched.error_reported = true;
+            return Some(GameEvent::ErrorReported);
+        }
+        let exit_code = match &watched.handle {
+            // no exit code yet means it's still running
+            Some(handle) => Some(handle.exit_code()?),
+            None => {
+                let running = system_info
+                    .processes()
+                    .iter()
+                    .any(|process| process.pid() == watched.process.pid());
+                if running {
+                    return None;
+                }
+                None
+            }
+        };
+        let watched = self.watched.take()?;
+        let exit = exit_cause(&watched, exit_code, force_close);
+        log::info!("Game exited ({exit}, exit code {exit_code:?})");
+        Some(GameEvent::Exited(watched.into_run(exit)))
+    }
+
+    /// ends the current run where it is, for when we're closing before the game is
+    pub fn finish(&mut self) -> Option<Run> {
+        let run = self.watched.take()?.into_run(ExitCause::Unfinished);
+        log::info!("Closing while the game is still running");
+        Some(run)
+    }
+
+    fn find_game(&mut self, system_info: &SystemInfo) -> Option<GameEvent> {
+        let (version, process) = LaunchVersion::iter().find_map(|version| {
+            let process = *system_info.find_by_names(&[version.exe()], false).first()?;
+            Some((version, process))
+        })?;
+        let handle = process
+            .watch()
+            .inspect_err(|why| log::info!("Couldn't open the game to watch it: {why}"))
+            .ok();
+        log::info!("Game started ({version}, pid {})", process.pid());
+        self.watched = Some(Watched {
+            process: process.clone(),
+            handle,
+            version,
+            started: SystemTime::now(),
+            anti_afk: Duration::ZERO,
+            error_reported: false,
+        });
+        Some(GameEvent::Started(version))
+    }
+}
+
+/// error reporting is started as a child of the process which crashed
+fn is_error_reported(system_info: &SystemInfo, game: &Process) -> bool {
+    system_info
+        .find_by_names(&[EXE_WER], false)
+        .iter()
+        .any(|process| process.is_child_of(game))
+}
+
+/// a crash report wins over our own force close, since we may have closed it because of that
+fn exit_cause(watched: &Watched, exit_code: Option<u32>, force_close: &ForceClose) -> ExitCause {
+    if watched.error_reported {
+        ExitCause::Crash
+    } else if force_close.has_closed(watched.process.pid()) {
+        ExitCause::ForceClosed
+    } else if exit_code.is_some_and(|code| code >= NTSTATUS_ERROR) {
+        ExitCause::Crash
+    } else {
+        ExitCause::Normal
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    gui::settings::HangSettings,
+    util::{consts::game::WINDOW_TITLE, win},
+};
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// somewhere to ask whether the game's window is responding
+pub trait WindowSource: Debug {
+    /// None when there's no window at all
+    fn is_responding(&self) -> Option<bool>;
+}
+
+#[derive(Debug)]
+pub struct GameWindow;
+
+impl WindowSource for GameWindow {
+    fn is_responding(&self) -> Option<bool> {
+        win::is_window_responding(WINDOW_TITLE)
+    }
+}
+
+/// a window which is whatever it's told to be
+#[derive(Debug, Default)]
+pub struct FakeWindow {
+    pub responding: Option<bool>,
+}
+
+impl WindowSource for FakeWindow {
+    fn is_responding(&self) -> Option<bool> {
+        self.responding
+    }
+}
+
+#[derive(Debug)]
+pub struct HangMonitor {
+    source: Box<dyn WindowSource>,
+    unresponsive_since: Option<Instant>,
+    last_poll: Instant,
+    dismissed: bool,
+}
+
+impl Default for HangMonitor {
+    fn default() -> Self {
+        Self {
+            source: Box::new(GameWindow),
+            unresponsive_since: None,
+            last_poll: Instant::now(),
+            dismissed: false,
+        }
+    }
+}
+
+impl HangMonitor {
+    pub fn set_source(&mut self, source: Box<dyn WindowSource>) {
+        self.source = source;
+        self.unresponsive_since = None;
+        self.dismissed = false;
+    }
+
+    pub fn run_timers(&mut self, settings: &HangSettings) {
+        if !settings.enabled || self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+        self.update(self.source.is_responding(), Instant::now());
+    }
+
+    /// keeps track of when the window stopped responding, forgetting it once it responds again
+    fn update(&mut self, responding: Option<bool>, now: Instant) {
+        if responding == Some(false) {
+            self.unresponsive_since.get_or_insert(now);
+        } else {
+            self.unresponsive_since = None;
+            self.dismissed = false;
+        }
+    }
+
+    /// how long the window has been unresponsive for, once that's past the threshold
+    pub fn hung_for(&self, settings: &HangSettings, now: Instant) -> Option<Duration> {
+        if !settings.enabled || self.dismissed {
+            return None;
+        }
+        let hung_for = now.saturating_duration_since(self.unresponsive_since?);
+        (hung_for >= Duration::from_secs(settings.threshold_secs)).then_some(hung_for)
+    }
+
+    /// stops suggesting anything until the window has responded again
+    pub const fn dismiss(&mut self) {
+        self.dismissed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: HangSettings = HangSettings {
+        enabled: true,
+        threshold_secs: 10,
+    };
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn threshold() {
+        let (mut monitor, start) = (HangMonitor::default(), Instant::now());
+        monitor.update(Some(true), start);
+        assert_eq!(monitor.hung_for(&SETTINGS, start + secs(60)), None);
+        monitor.update(Some(false), start + secs(1));
+        // it's still hung since the first time it didn't respond, not the latest
+        monitor.update(Some(false), start + secs(5));
+        assert_eq!(monitor.hung_for(&SETTINGS, start + secs(10)), None);
+        assert_eq!(
+            monitor.hung_for(&SETTINGS, start + secs(11)),
+            Some(secs(10))
+        );
+        assert_eq!(
+            monitor.hung_for(&SETTINGS, start + secs(20)),
+            Some(secs(19))
+        );
+        let disabled = HangSettings {
+            enabled: false,
+            ..SETTINGS
+        };
+        assert_eq!(monitor.hung_for(&disabled, start + secs(20)), None);
+    }
+
+    #[test]
+    fn dismiss_until_responding() {
+        let (mut monitor, start) = (HangMonitor::default(), Instant::now());
+        monitor.update(Some(false), start);
+        monitor.dismiss();
+        monitor.update(Some(false), start + secs(30));
+        assert_eq!(monitor.hung_for(&SETTINGS, start + secs(30)), None);
+        // responding again re-arms it, and the next hang counts from when that one started
+        monitor.update(Some(true), start + secs(31));
+        monitor.update(Some(false), start + secs(32));
+        assert_eq!(monitor.hung_for(&SETTINGS, start + secs(41)), None);
+        assert_eq!(
+            monitor.hung_for(&SETTINGS, start + secs(42)),
+            Some(secs(10))
+        );
+    }
+
+    #[test]
+    fn missing_window() {
+        let (mut monitor, start) = (HangMonitor::default(), Instant::now());
+        monitor.update(Some(false), start);
+        monitor.dismiss();
+        // the game closing counts as it no longer being hung
+        monitor.update(None, start + secs(20));
+        assert_eq!(monitor.hung_for(&SETTINGS, start + secs(60)), None);
+        assert!(!monitor.dismissed);
+
+        let mut monitor = HangMonitor::default();
+        monitor.set_source(Box::new(FakeWindow { responding: None }));
+        monitor.last_poll -= POLL_INTERVAL;
+        monitor.run_timers(&SETTINGS);
+        assert_eq!(monitor.unresponsive_since, None);
+        monitor.set_source(Box::new(FakeWindow {
+            responding: Some(false),
+        }));
+        monitor.last_poll -= POLL_INTERVAL;
+        monitor.run_timers(&SETTINGS);
+        assert!(monitor.unresponsive_since.is_some());
+    }
+}
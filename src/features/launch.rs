@@ -1,8 +1,33 @@
-use crate::gui::settings::LaunchVersion;
+use crate::{
+    gui::settings::{LaunchArgs, LaunchVersion},
+    util::{
+        consts::{
+            game::{
+                EPIC_APP_ENHANCED, EPIC_APP_LEGACY, EXE_EPIC, EXE_PLAY, EXE_STEAM, LAUNCHER_EXES,
+                STEAM_APP_ENHANCED, STEAM_APP_LEGACY, WINDOW_TITLE,
+            },
+            path,
+        },
+        installs::{self, Install},
+        system_info::SystemInfo,
+        win,
+    },
+};
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, process::Command};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
 use strum::{Display, EnumIter};
-use winreg::{RegKey, enums::HKEY_LOCAL_MACHINE};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const LAUNCHER_TIMEOUT: Duration = Duration::from_secs(60);
+/// long enough for the launcher to log in and update the game
+const GAME_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const WINDOW_TIMEOUT: Duration = Duration::from_secs(2 * 60);
 
 #[derive(Clone, Copy, Default, Debug, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
 pub enum Platform {
@@ -14,50 +39,326 @@ pub enum Platform {
     Epic,
 }
 
+impl Platform {
+    /// processes which show that the platform's launcher is up and running
+    pub fn launcher_exes(self) -> Vec<&'static str> {
+        match self {
+            Self::Steam => vec![EXE_STEAM],
+            Self::Rockstar => [EXE_PLAY].into_iter().chain(LAUNCHER_EXES).collect(),
+            Self::Epic => vec![EXE_EPIC],
+        }
+    }
+}
+
+/// things to do once the game's process shows up after a profile launched it
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum PostLaunchAction {
+    #[strum(to_string = "Enable anti AFK")]
+    EnableAntiAfk,
+    #[strum(to_string = "Start AFK session")]
+    StartAfkSession,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub platform: Platform,
+    pub version: LaunchVersion,
+    pub args: LaunchArgs,
+    /// block the game's network with the configured block method before launching
+    pub pre_block: bool,
+    pub post_launch: Vec<PostLaunchAction>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: String::from("Default"),
+            platform: Platform::default(),
+            version: LaunchVersion::default(),
+            args: LaunchArgs::default(),
+            pre_block: false,
+            post_launch: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Progress {
+    WaitingForLauncher,
+    WaitingForGame,
+    WaitingForWindow,
+    Started,
+    TimedOut(&'static str),
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct Tracking {
+    progress: Progress,
+    platform: Platform,
+    version: LaunchVersion,
+    actions: Vec<PostLaunchAction>,
+    phase_started: Instant,
+    last_poll: Instant,
+}
+
 #[derive(Debug, Default)]
 pub struct Launch {
-    pub selected: Platform,
+    pub profiles: Vec<Profile>,
+    pub selected: usize,
+    pub installs: Vec<Install>,
+    /// where rockstar's own processes are allowed to live, see [`installs::rockstar_dirs`]
+    pub rockstar_dirs: Vec<PathBuf>,
+    /// the profile which was launched last, for relaunching after a crash
+    pub last: Option<Profile>,
+    tracking: Option<Tracking>,
 }
 
-pub fn launch(platform: &Platform, version: &LaunchVersion) {
+impl Launch {
+    /// looks for installs again, creating a profile for the first one found if there are none
+    pub fn discover(&mut self) {
+        self.installs = installs::discover();
+        self.rockstar_dirs = installs::rockstar_dirs(&self.installs);
+        for install in &self.installs {
+            log::info!(
+                "Found {} {} install at {} (build {})",
+                install.platform,
+                install.version,
+                install.path.display(),
+                install.build_id.as_deref().unwrap_or("unknown"),
+            );
+        }
+        if self.profiles.is_empty() {
+            let mut profile = Profile::default();
+            if let Some(install) = self.installs.first() {
+                profile.platform = install.platform;
+                profile.version = install.version;
+            }
+            self.profiles.push(profile);
+        }
+        self.selected = self.selected.min(self.profiles.len() - 1);
+    }
+
+    pub fn install(&self, platform: Platform, version: LaunchVersion) -> Option<&Install> {
+        self.installs
+            .iter()
+            .find(|install| install.platform == platform && install.version == version)
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profiles.get(self.selected)
+    }
+
+    pub fn find_profile(&self, name: &str) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// launches a profile and starts following its progress
+    pub fn launch_profile(&mut self, profile: &Profile) {
+        let progress = match launch(&profile.platform, &profile.version, &profile.args.to_args()) {
+            Ok(()) => Progress::WaitingForLauncher,
+            Err(why) => {
+                log::warn!("Couldn't launch {}: {why}", profile.name);
+                Progress::Failed(why.to_string())
+            }
+        };
+        self.last = Some(profile.clone());
+        self.tracking = Some(Tracking {
+            progress,
+            platform: profile.platform,
+            version: profile.version,
+            actions: profile.post_launch.clone(),
+            phase_started: Instant::now(),
+            last_poll: Instant::now(),
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.tracking.as_ref().is_some_and(|tracking| {
+            matches!(
+                tracking.progress,
+                Progress::WaitingForLauncher
+                    | Progress::WaitingForGame
+                    | Progress::WaitingForWindow
+            )
+        })
+    }
+
+    pub fn status(&self) -> Option<String> {
+        let tracking = self.tracking.as_ref()?;
+        let status = match &tracking.progress {
+            Progress::WaitingForLauncher => format!("Waiting for {}...", tracking.platform),
+            Progress::WaitingForGame => "Launcher started, waiting for the game...".to_owned(),
+            Progress::WaitingForWindow => "Game started, waiting for its window...".to_owned(),
+            Progress::Started => "Launched.".to_owned(),
+            Progress::TimedOut(what) => format!("Launch timed out: {what}."),
+            Progress::Failed(why) => format!("Launch failed: {why}."),
+        };
+        Some(status)
+    }
+
+    /// forgets about a finished launch, so its status stops being shown
+    pub fn dismiss(&mut self) {
+        if !self.is_running() {
+            self.tracking = None;
+        }
+    }
+
+    /// follows the launch along, returning the post launch actions once the game's window is up
+    pub fn run_timers(&mut self, system_info: &mut SystemInfo) -> Vec<PostLaunchAction> {
+        if !self.is_running() {
+            return Vec::new();
+        }
+        let Some(tracking) = &mut self.tracking else {
+            return Vec::new();
+        };
+        if tracking.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        tracking.last_poll = Instant::now();
+        system_info.refresh();
+        let game_running = !system_info
+            .find_by_names(&[tracking.version.exe()], false)
+            .is_empty();
+        let elapsed = tracking.phase_started.elapsed();
+        let next = match tracking.progress {
+            // the game showing up means the launcher must have started, even if we missed it
+            Progress::WaitingForLauncher if game_running => Some(Progress::WaitingForWindow),
+            Progress::WaitingForLauncher
+                if !system_info
+                    .find_by_names(&tracking.platform.launcher_exes(), false)
+                    .is_empty() =>
+            {
+                Some(Progress::WaitingForGame)
+            }
+            Progress::WaitingForLauncher if elapsed >= LAUNCHER_TIMEOUT => {
+                Some(Progress::TimedOut("the launcher never started"))
+            }
+            Progress::WaitingForGame if game_running => Some(Progress::WaitingForWindow),
+            Progress::WaitingForGame if elapsed >= GAME_TIMEOUT => {
+                Some(Progress::TimedOut("the game never started"))
+            }
+            Progress::WaitingForWindow if win::is_window_present(WINDOW_TITLE) => {
+                Some(Progress::Started)
+            }
+            Progress::WaitingForWindow if elapsed >= WINDOW_TIMEOUT => {
+                Some(Progress::TimedOut("the game's window never showed up"))
+            }
+            _ => None,
+        };
+        let Some(next) = next else {
+            return Vec::new();
+        };
+        tracking.progress = next;
+        tracking.phase_started = Instant::now();
+        match &tracking.progress {
+            Progress::Started => std::mem::take(&mut tracking.actions),
+            Progress::TimedOut(what) => {
+                log::warn!("Launch timed out: {what}");
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub fn launch(platform: &Platform, version: &LaunchVersion, args: &[String]) -> Result<()> {
     match platform {
         Platform::Steam => {
-            let steam_url = match version {
-                LaunchVersion::Enhanced => "steam://run/3240220",
-                LaunchVersion::Legacy => "steam://run/271590",
+            let app_id = match version {
+                LaunchVersion::Enhanced => STEAM_APP_ENHANCED,
+                LaunchVersion::Legacy => STEAM_APP_LEGACY,
+            };
+            // steam takes the arguments as an extra path segment of the run url
+            let steam_url = if args.is_empty() {
+                format!("steam://run/{app_id}")
+            } else {
+                format!("steam://run/{app_id}//{}/", percent_encode(&args.join(" ")))
             };
-            open::that_detached(steam_url).unwrap();
+            open::that_detached(steam_url).context("Steam doesn't seem to be installed")?;
         }
         Platform::Rockstar => {
-            let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-            let rockstar_url = match version {
-                LaunchVersion::Enhanced => r"SOFTWARE\WOW6432Node\Rockstar Games\GTAV Enhanced",
-                LaunchVersion::Legacy => r"SOFTWARE\WOW6432Node\Rockstar Games\Grand Theft Auto V",
-            };
-            let Ok(gta_key) = hklm.open_subkey(rockstar_url) else {
-                return;
-            };
-            let Ok(install_folder): Result<String, std::io::Error> =
-                gta_key.get_value("InstallFolder")
-            else {
-                return;
-            };
-            let mut exe_path = PathBuf::from(install_folder);
-            exe_path.push("PlayGTAV.exe");
-            if let Err(why) = Command::new(exe_path).spawn() {
-                log::warn!("Couldn't launch GTA 5 via Rockstar Games Launcher: {why}");
-            }
+            let install = installs::rockstar(*version).with_context(|| {
+                format!("GTA 5 {version} isn't installed via Rockstar Games Launcher")
+            })?;
+            Command::new(install.path.join(EXE_PLAY))
+                .args(args)
+                .spawn()
+                .with_context(|| format!("couldn't start {EXE_PLAY}"))?;
         }
         Platform::Epic => {
-            let epic_url = match version {
-                LaunchVersion::Enhanced => {
-                    "com.epicgames.launcher://apps/8769e24080ea413b8ebca3f1b8c50951?action=launch&silent=true"
-                }
-                LaunchVersion::Legacy => {
-                    "com.epicgames.launcher://apps/9d2d0eb64d5c44529cece33fe2a46482?action=launch&silent=true"
-                }
+            let app_name = match version {
+                LaunchVersion::Enhanced => EPIC_APP_ENHANCED,
+                LaunchVersion::Legacy => EPIC_APP_LEGACY,
             };
-            open::that_detached(epic_url).unwrap();
+            // the epic launch url has no way to pass arguments along, but the game itself
+            // reads extra arguments from commandline.txt next to its exe
+            write_commandline(*version, args);
+            open::that_detached(format!(
+                "com.epicgames.launcher://apps/{app_name}?action=launch&silent=true"
+            ))
+            .context("Epic Games Launcher doesn't seem to be installed")?;
+        }
+    }
+    Ok(())
+}
+
+/// the user may have written commandline.txt themselves, so it's only touched while it's
+/// missing or still holds what we last wrote, and removed again once there are no arguments
+fn write_commandline(version: LaunchVersion, args: &[String]) {
+    let Some(install) = installs::epic(version) else {
+        if !args.is_empty() {
+            log::warn!(
+                "Couldn't find GTA 5 {version} installed via Epic Games to pass arguments to"
+            );
         }
+        return;
+    };
+    let commandline = install.path.join("commandline.txt");
+    let written = path::app_epic_commandline(version);
+    let ours = match fs::read_to_string(&commandline) {
+        Ok(current) => fs::read_to_string(&written).is_ok_and(|written| written == current),
+        Err(_) => true,
+    };
+    if !ours {
+        if !args.is_empty() {
+            log::warn!(
+                "Not passing launch arguments to Epic Games, as {} wasn't written by GTA Tools",
+                commandline.display()
+            );
+        }
+        return;
+    }
+    let result = if args.is_empty() {
+        remove_if_present(&commandline).and_then(|()| remove_if_present(&written))
+    } else {
+        let args = args.join(" ");
+        // remembered first, so that a file we managed to write is always known to be ours
+        fs::write(&written, &args).and_then(|()| fs::write(&commandline, &args))
+    };
+    if let Err(why) = result {
+        log::warn!("Couldn't update launch arguments for Epic Games: {why}");
+    }
+}
+
+fn remove_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
     }
 }
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
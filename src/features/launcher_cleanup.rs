@@ -0,0 +1,80 @@
+use crate::{
+    features::force_close::ForceClose, gui::settings::CleanupSettings,
+    util::system_info::SystemInfo,
+};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// closes whatever the launchers left running once the game has been gone for a while
+#[derive(Debug, Default)]
+pub struct LauncherCleanup {
+    cleanup_at: Option<Instant>,
+}
+
+impl LauncherCleanup {
+    pub fn schedule(&mut self, settings: &CleanupSettings) {
+        if !settings.enabled || settings.exes.is_empty() {
+            return;
+        }
+        log::info!(
+            "Closing leftover launcher processes in {}s",
+            settings.grace_secs
+        );
+        self.cleanup_at = Some(Instant::now() + Duration::from_secs(settings.grace_secs));
+    }
+
+    /// leaves the launchers running, with `reason` saying why for the log
+    pub fn cancel(&mut self, reason: &str) {
+        if self.cleanup_at.take().is_some() {
+            log::info!("Not closing leftover launchers: {reason}");
+        }
+    }
+
+    pub fn status(&self) -> Option<String> {
+        let cleanup_at = self.cleanup_at?;
+        Some(format!(
+            "Closing leftover launchers in {}s.",
+            cleanup_at
+                .saturating_duration_since(Instant::now())
+                .as_secs()
+        ))
+    }
+
+    /// `relaunching` cancels a pending cleanup, since the launchers are about to be used.
+    /// closing goes through the app's force close, so that its outcomes are shown the same way
+    pub fn run_timers(
+        &mut self,
+        system_info: &mut SystemInfo,
+        force_close: &mut ForceClose,
+        settings: &CleanupSettings,
+        rockstar_dirs: &[PathBuf],
+        relaunching: bool,
+    ) {
+        if relaunching {
+            self.cancel("the game is being launched again");
+        }
+        // waiting out a force close that's already running, rather than cutting it short
+        match self.cleanup_at {
+            Some(cleanup_at) if Instant::now() >= cleanup_at && !force_close.is_running() => {
+                self.cleanup_at = None;
+            }
+            _ => return,
+        }
+        system_info.refresh();
+        let exes = settings.exes.iter().map(String::as_str).collect::<Vec<_>>();
+        // only what rockstar installed, since names like Launcher.exe are far from unique
+        let targets = system_info
+            .find_by_names(&exes, false)
+            .into_iter()
+            .filter(|process| process.is_inside(rockstar_dirs))
+            .cloned()
+            .collect::<Vec<_>>();
+        if targets.is_empty() {
+            log::info!("No leftover launcher processes to close");
+            return;
+        }
+        force_close.close(targets, true);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    gui::settings::LaunchVersion,
+    util::{consts::path, win},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use strum::Display;
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitCause {
+    #[strum(to_string = "exited")]
+    Normal,
+    #[strum(to_string = "force closed")]
+    ForceClosed,
+    #[strum(to_string = "crashed")]
+    Crash,
+    /// GTA Tools closed while the game was still running, so that's where the run ends
+    #[strum(to_string = "still running")]
+    Unfinished,
+}
+
+/// a single run of the game, from its process starting to it exiting
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Run {
+    pub started: SystemTime,
+    pub ended: SystemTime,
+    pub version: LaunchVersion,
+    pub exit: ExitCause,
+    /// how much of the run was spent with anti AFK enabled
+    pub anti_afk: Duration,
+}
+
+impl Run {
+    pub fn duration(&self) -> Duration {
+        self.ended.duration_since(self.started).unwrap_or_default()
+    }
+}
+
+/// a local calendar day, counted in days since the unix epoch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Day(i64);
+
+impl Day {
+    pub fn of(time: SystemTime) -> Self {
+        Self(local_secs(time).div_euclid(SECS_PER_DAY))
+    }
+
+    pub fn today() -> Self {
+        Self::of(SystemTime::now())
+    }
+
+    /// the monday of the week this day is in
+    pub const fn week_start(self) -> Self {
+        // the epoch was a thursday, which is 3 days after a monday
+        Self(self.0 - (self.0 + 3).rem_euclid(7))
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.0);
+        write!(f, "{year}-{month:02}-{day:02}")
+    }
+}
+
+/// every run of the game we've seen, kept in a file in our storage
+#[derive(Debug, Default)]
+pub struct Playtime {
+    pub runs: Vec<Run>,
+}
+
+impl Playtime {
+    pub fn load() -> Self {
+        let runs = fs::read_to_string(path::APP_PLAYTIME.as_path())
+            .ok()
+            .and_then(|json| {
+                serde_json::from_str(&json)
+                    .inspect_err(|why| log::warn!("Couldn't read playtime history: {why}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self { runs }
+    }
+
+    pub fn record(&mut self, run: Run) {
+        self.runs.push(run);
+        if let Err(why) = self.save() {
+            log::warn!("Couldn't save playtime history: {why}");
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.runs)?;
+        fs::write(path::APP_PLAYTIME.as_path(), json)?;
+        Ok(())
+    }
+
+    /// total playtime per day, newest first
+    pub fn daily(&self) -> Vec<(Day, Duration)> {
+        self.totals(|day| day)
+    }
+
+    /// total playtime per week, keyed by the week's monday, newest first
+    pub fn weekly(&self) -> Vec<(Day, Duration)> {
+        self.totals(Day::week_start)
+    }
+
+    /// runs are counted towards the day they started on, even if they went past midnight
+    fn totals(&self, key: impl Fn(Day) -> Day) -> Vec<(Day, Duration)> {
+        let mut totals = BTreeMap::<Day, Duration>::new();
+        for run in &self.runs {
+            *totals.entry(key(Day::of(run.started))).or_default() += run.duration();
+        }
+        totals.into_iter().rev().collect()
+    }
+
+    /// writes every run to a csv file, returning where it was written
+    pub fn export_csv(&self) -> Result<PathBuf> {
+        let mut csv = String::from("started,ended,version,exit,duration_secs,anti_afk_secs\n");
+        for run in &self.runs {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                humantime::format_rfc3339_seconds(run.started),
+                humantime::format_rfc3339_seconds(run.ended),
+                run.version,
+                run.exit,
+                run.duration().as_secs(),
+                run.anti_afk.as_secs(),
+            )?;
+        }
+        fs::write(path::APP_PLAYTIME_CSV.as_path(), csv)?;
+        Ok(path::APP_PLAYTIME_CSV.clone())
+    }
+}
+
+/// formats a time as local "YYYY-MM-DD HH:MM"
+pub fn local_timestamp(time: SystemTime) -> String {
+    let secs = local_secs(time).rem_euclid(SECS_PER_DAY);
+    format!(
+        "{} {:02}:{:02}",
+        Day::of(time),
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+/// seconds since the unix epoch, shifted into the local time of that moment
+fn local_secs(time: SystemTime) -> i64 {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => i64::try_from(since.as_secs()).unwrap_or(i64::MAX),
+        Err(before) => -i64::try_from(before.duration().as_secs()).unwrap_or(i64::MAX),
+    };
+    secs + win::utc_offset_at(secs)
+}
+
+/// turns days since the unix epoch into a (year, month, day) date, see
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
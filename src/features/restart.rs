@@ -0,0 +1,124 @@
+use crate::{
+    features::{
+        force_close::{self, ForceClose},
+        launch::Profile,
+    },
+    gui::settings::{ForceCloseSettings, ForceCloseTargets},
+    util::system_info::{Process, SystemInfo},
+};
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const EXIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    Closing,
+    WaitingForExit,
+    Done,
+    TimedOut(&'static str),
+}
+
+#[derive(Debug)]
+pub struct Restart {
+    phase: Option<Phase>,
+    phase_started: Instant,
+    last_poll: Instant,
+    profile: Profile,
+}
+
+impl Default for Restart {
+    fn default() -> Self {
+        Self {
+            phase: None,
+            phase_started: Instant::now(),
+            last_poll: Instant::now(),
+            profile: Profile::default(),
+        }
+    }
+}
+
+impl Restart {
+    pub fn start(
+        &mut self,
+        force_close: &mut ForceClose,
+        system_info: &mut SystemInfo,
+        settings: &ForceCloseSettings,
+        rockstar_dirs: &[PathBuf],
+        game: Option<&Process>,
+        profile: Profile,
+    ) {
+        force_close.activate(system_info, &close_settings(settings), rockstar_dirs, game);
+        self.profile = profile;
+        self.enter(Phase::Closing);
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.phase, Some(Phase::Closing | Phase::WaitingForExit))
+    }
+
+    pub fn status(&self) -> Option<String> {
+        let status = match self.phase? {
+            Phase::Closing => "Restart: closing...".to_owned(),
+            Phase::WaitingForExit => "Restart: waiting for exit...".to_owned(),
+            Phase::Done => "Restart: game closed, launching again.".to_owned(),
+            Phase::TimedOut(what) => format!("Restart timed out: {what}."),
+        };
+        Some(status)
+    }
+
+    /// returns the profile to launch again once everything has exited
+    pub fn run_timers(
+        &mut self,
+        force_close: &ForceClose,
+        system_info: &mut SystemInfo,
+        settings: &ForceCloseSettings,
+        rockstar_dirs: &[PathBuf],
+    ) -> Option<Profile> {
+        let phase = self.phase?;
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+        match phase {
+            Phase::Closing if !force_close.is_running() => self.enter(Phase::WaitingForExit),
+            Phase::WaitingForExit => {
+                system_info.refresh();
+                let settings = close_settings(settings);
+                if force_close::targets(system_info, &settings, rockstar_dirs, None).is_empty() {
+                    // from here on the launch follows its own progress
+                    self.enter(Phase::Done);
+                    return Some(self.profile.clone());
+                } else if self.phase_started.elapsed() >= EXIT_TIMEOUT {
+                    self.enter(Phase::TimedOut("the game didn't exit"));
+                }
+            }
+            Phase::Closing | Phase::Done | Phase::TimedOut(_) => {}
+        }
+        None
+    }
+
+    /// forgets about a finished restart, so its status stops being shown
+    pub fn dismiss(&mut self) {
+        if !self.is_running() {
+            self.phase = None;
+        }
+    }
+
+    fn enter(&mut self, phase: Phase) {
+        self.phase = Some(phase);
+        self.phase_started = Instant::now();
+    }
+}
+
+/// a leftover launcher blocks the next launch, so a restart always closes the launchers too
+pub fn close_settings(settings: &ForceCloseSettings) -> ForceCloseSettings {
+    let mut settings = settings.clone();
+    if settings.targets == ForceCloseTargets::GameOnly {
+        settings.targets = ForceCloseTargets::GameAndLaunchers;
+    }
+    settings
+}
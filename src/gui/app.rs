@@ -1,29 +1,45 @@
 use crate::{
-    features::{self, game_networking::BlockedStatus},
+    features::{
+        self,
+        afk_macro::{Macro, NamedMacro},
+        game_networking::BlockedStatus,
+        game_watcher::GameEvent,
+        launch::{Platform, PostLaunchAction, Profile},
+        playtime::{Day, ExitCause, Run, local_timestamp},
+    },
     gui::{
-        settings::{BlockMethod, ROCKSTAR_SAVE_SERVER, Settings},
+        settings::{
+            BlockMethod, DEFAULT_MACRO, EndAction, ForceCloseSettings, LaunchFlag,
+            ROCKSTAR_SAVE_SERVER, SessionLimit, Settings,
+        },
         tools,
-        ui_ext::UiExt,
+        ui_ext::{Confirm, UiExt},
     },
     util::{
-        consts::{colours, game::WINDOW_TITLE, path},
+        consts::{colours, game::LEFTOVER_EXES, path},
         firewall::Firewall,
-        persistent_state::PersistentState,
-        system_info::SystemInfo,
+        input::Key,
+        persistent_state::{AutoSave, ConfigIssue, PersistentState},
+        storage,
+        system_info::{KillError, SystemInfo},
         win,
     },
 };
 use eframe::egui;
 use std::time::{Duration, Instant};
-use strum::{Display, EnumIter};
+use strum::{Display, EnumIter, IntoEnumIterator};
 
 pub const WINDOW_SIZE: [f32; 2] = [240.0, 240.0];
+const HISTORY_DAYS: usize = 14;
+const HISTORY_WEEKS: usize = 8;
+const HISTORY_RUNS: usize = 100;
 
 #[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, EnumIter)]
 enum Stage {
     #[default]
     Main,
     Settings,
+    Stats,
     About,
 }
 
@@ -31,6 +47,11 @@ enum Stage {
 pub struct Flags {
     pub elevated: bool,
     pub debug: bool,
+    #[cfg(any(test, debug_assertions))]
+    pub dry_run: bool,
+    pub fake_hang: bool,
+    #[cfg(any(test, debug_assertions))]
+    pub fake_tuning: bool,
     closing: bool,
 }
 
@@ -39,21 +60,48 @@ impl Default for Flags {
         Self {
             elevated: win::is_elevated(),
             debug: false,
+            #[cfg(any(test, debug_assertions))]
+            dry_run: false,
+            fake_hang: false,
+            #[cfg(any(test, debug_assertions))]
+            fake_tuning: false,
             closing: false,
         }
     }
 }
 
+#[derive(Debug, Default)]
+struct Confirms {
+    force_close: Confirm,
+    restart: Confirm,
+    unblock: Confirm,
+    remove_rules: Confirm,
+    reset_settings: Confirm,
+}
+
 #[derive(Debug, Default)]
 pub struct App {
     pub settings: Settings,
     stage: Stage,
+    confirms: Confirms,
     pub flags: Flags,
     pub system_info: SystemInfo,
     firewall: Firewall,
     pub anti_afk: features::anti_afk::AntiAfk,
+    afk_session: features::afk_session::AfkSession,
     empty_session: features::empty_session::EmptySession,
     force_close: features::force_close::ForceClose,
+    restart: features::restart::Restart,
+    pub game_watcher: features::game_watcher::GameWatcher,
+    crash_recovery: features::crash_recovery::CrashRecovery,
+    pub hang_monitor: features::hang_monitor::HangMonitor,
+    launcher_cleanup: features::launcher_cleanup::LauncherCleanup,
+    pub game_tuning: features::game_tuning::GameTuning,
+    pub game_build: features::game_build::GameBuild,
+    /// why some or all of the config wasn't loaded, until the user has seen it
+    pub config_issue: Option<ConfigIssue>,
+    pub auto_save: AutoSave,
+    pub playtime: features::playtime::Playtime,
     pub game_networking: features::game_networking::GameNetworking,
     pub launch: features::launch::Launch,
 }
@@ -62,6 +110,46 @@ impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         ctx.request_repaint_after(Duration::from_millis(100));
         self.empty_session.run_timers(&self.firewall).unwrap();
+        self.anti_afk.run_timers(&self.settings.anti_afk);
+        self.force_close.run_timers();
+        if let Some(event) = self.game_watcher.run_timers(
+            &mut self.system_info,
+            &self.force_close,
+            self.anti_afk.enabled,
+        ) {
+            self.handle_game_event(event);
+        }
+        self.hang_monitor.run_timers(&self.settings.hang);
+        if self.crash_recovery.run_timers(&mut self.anti_afk) {
+            self.relaunch();
+        }
+        self.launcher_cleanup.run_timers(
+            &mut self.system_info,
+            &mut self.force_close,
+            &self.settings.cleanup,
+            &self.launch.rockstar_dirs,
+            self.launch.is_running()
+                || self.restart.is_running()
+                || self.crash_recovery.is_relaunch_pending(),
+        );
+        for action in self.launch.run_timers(&mut self.system_info) {
+            self.run_post_launch_action(action);
+        }
+        if let Some(profile) = self.restart.run_timers(
+            &self.force_close,
+            &mut self.system_info,
+            &self.settings.force_close,
+            &self.launch.rockstar_dirs,
+        ) {
+            self.launch_game(&profile);
+        }
+        if let Some(end_action) = self
+            .afk_session
+            .run_timers(self.settings.afk_session.end_action, &self.anti_afk)
+        {
+            self.run_end_action(end_action);
+        }
+        self.autosave();
         egui::TopBottomPanel::bottom("bottom_panel")
             .exact_height(25.0)
             .show(ctx, |ui| {
@@ -73,7 +161,7 @@ impl eframe::App for App {
                             .on_hover_text("Relaunch ourselves as administrator.")
                             .on_disabled_hover_text("We are already running elevated.");
                         if button.clicked() {
-                            win::elevate(win::ElevationExitMethod::Gentle(&mut self.flags.closing));
+                            self.elevate();
                         }
                     });
                 });
@@ -84,6 +172,7 @@ impl eframe::App for App {
                 .show(ui, |ui| match self.stage {
                     Stage::Main => self.show_main_stage(ctx, ui),
                     Stage::Settings => self.show_settings_stage(ctx, ui),
+                    Stage::Stats => self.show_stats_stage(ctx, ui),
                     Stage::About => self.show_about_stage(ctx, ui),
                 });
         });
@@ -100,22 +189,401 @@ impl eframe::App for App {
 }
 
 impl App {
+    /// launches a profile, blocking the game's network first if it asks for that
+    pub fn launch_profile(&mut self, index: usize) {
+        let Some(profile) = self.launch.profiles.get(index).cloned() else {
+            return;
+        };
+        self.launch.selected = index;
+        self.crash_recovery.reset();
+        self.launch_game(&profile);
+    }
+
+    /// every launch goes through here, so that none of them skip blocking the network first
+    fn launch_game(&mut self, profile: &Profile) {
+        if profile.pre_block {
+            self.pre_block(profile);
+        }
+        self.launch.launch_profile(profile);
+    }
+
+    /// the game isn't running yet, so the exe is blocked by its path in the install
+    fn pre_block(&mut self, profile: &Profile) {
+        let result = match self.settings.block_method {
+            BlockMethod::EntireGame => {
+                let Some(install) = self.launch.install(profile.platform, profile.version) else {
+                    log::warn!("Couldn't block the game's network, as its install wasn't found");
+                    return;
+                };
+                let exe_path = install.path.join(profile.version.exe());
+                self.game_networking
+                    .block_exe_path(exe_path, &self.firewall)
+            }
+            BlockMethod::SaveServer => self
+                .game_networking
+                .block_save_server(&self.settings.save_server_ip, &self.firewall),
+        };
+        if let Err(why) = result {
+            log::warn!("Couldn't block the game's network before launching: {why}");
+        }
+    }
+
+    fn handle_game_event(&mut self, event: GameEvent) {
+        match event {
+            GameEvent::Started(version) => {
+                self.launcher_cleanup.cancel("the game started again");
+                self.game_build
+                    .on_started(version, self.game_watcher.exe_path());
+                if self.settings.tuning.auto_apply {
+                    self.apply_tuning();
+                }
+            }
+            GameEvent::ErrorReported => {
+                // the error dialog keeps the game's process around, which would block a relaunch
+                if self.settings.crash.auto_relaunch {
+                    self.force_close.activate(
+                        &mut self.system_info,
+                        &ForceCloseSettings::default(),
+                        &self.launch.rockstar_dirs,
+                        self.game_watcher.process(),
+                    );
+                }
+            }
+            GameEvent::Exited(run) => {
+                if run.exit == ExitCause::Crash {
+                    log::error!(
+                        "The game crashed after running for {}",
+                        format_minutes(run.duration())
+                    );
+                }
+                self.crash_recovery
+                    .on_exit(&run, &self.settings.crash, &mut self.anti_afk);
+                self.playtime.record(run);
+                self.game_build.on_exited();
+                self.launcher_cleanup.schedule(&self.settings.cleanup);
+            }
+        }
+    }
+
+    /// the elevated instance reads the config as it starts, so it has to be saved first
+    fn elevate(&mut self) {
+        self.save_config();
+        win::elevate(win::ElevationExitMethod::Gentle(&mut self.flags.closing));
+    }
+
+    fn persistent_state(&self) -> PersistentState {
+        PersistentState {
+            profiles: self.launch.profiles.clone(),
+            selected_profile: self.launch.selected,
+            last_builds: self.game_build.last_seen.clone(),
+            anti_afk_enabled: self.anti_afk.enabled,
+            settings: self.settings.clone(),
+            ..PersistentState::default()
+        }
+    }
+
+    /// saves the config, leaving a notice on the main page if that fails
+    pub fn save_config(&mut self) {
+        let result = self
+            .persistent_state()
+            .to_json()
+            .and_then(|json| PersistentState::write(&json).map(|()| json));
+        match result {
+            Ok(json) => self.auto_save.saved(json),
+            Err(why) => {
+                log::error!("Couldn't save the config: {why:#}");
+                self.auto_save.failed();
+                self.config_issue = Some(ConfigIssue::SaveFailed(format!("{why:#}")));
+            }
+        }
+    }
+
+    /// saves the config shortly after anything in it changes, so that nothing is lost
+    /// if we don't get to exit normally
+    fn autosave(&mut self) {
+        if !self.auto_save.is_check_due() {
+            return;
+        }
+        let Ok(json) = self.persistent_state().to_json() else {
+            return;
+        };
+        if self.auto_save.should_save(json) {
+            self.save_config();
+        }
+    }
+
+    pub fn apply_tuning(&mut self) {
+        if let Some(pid) = self.game_watcher.pid() {
+            self.game_tuning
+                .apply(pid, &self.settings.tuning, cpu_count());
+        }
+    }
+
+    /// launches whatever was launched last again, or the selected profile if nothing was
+    fn relaunch(&mut self) {
+        let profile = self
+            .launch
+            .last
+            .clone()
+            .or_else(|| self.launch.profile().cloned());
+        if let Some(profile) = profile {
+            self.launch_game(&profile);
+        }
+    }
+
+    fn run_post_launch_action(&mut self, action: PostLaunchAction) {
+        log::info!("Game started, running post launch action: {action}");
+        match action {
+            PostLaunchAction::EnableAntiAfk => self.anti_afk.enabled = true,
+            PostLaunchAction::StartAfkSession => {
+                if !self.afk_session.is_running() {
+                    self.afk_session
+                        .start(&self.settings.afk_session, &mut self.anti_afk);
+                }
+            }
+        }
+    }
+
+    fn run_end_action(&mut self, end_action: EndAction) {
+        log::info!("AFK session ended, running end action: {end_action}");
+        match end_action {
+            EndAction::Nothing => {}
+            EndAction::DisableAntiAfk => self.anti_afk.enabled = false,
+            EndAction::ForceCloseGame => {
+                self.anti_afk.enabled = false;
+                self.force_close.activate(
+                    &mut self.system_info,
+                    &self.settings.force_close,
+                    &self.launch.rockstar_dirs,
+                    self.game_watcher.process(),
+                );
+            }
+            EndAction::Sleep => {
+                self.anti_afk.enabled = false;
+                win::sleep();
+            }
+        }
+    }
+
     fn show_game_section(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
         ui.header("Game");
         ui.horizontal(|ui| {
-            if ui.button("Launch").clicked() {
-                features::launch::launch(&self.launch.selected, &self.settings.launch_version);
+            let installed = self
+                .launch
+                .profile()
+                .and_then(|profile| self.launch.install(profile.platform, profile.version))
+                .is_some();
+            let launching = self.launch.is_running();
+            if ui
+                .add_enabled(installed && !launching, egui::Button::new("Launch"))
+                .on_disabled_hover_text(if launching {
+                    "Already launching."
+                } else {
+                    "GTA 5 isn't installed for this profile."
+                })
+                .clicked()
+            {
+                self.launch_profile(self.launch.selected);
             }
-            egui::ComboBox::from_id_salt("Launch")
-                .selected_text(self.launch.selected.to_string())
+            egui::ComboBox::from_id_salt("Launch profile")
+                .selected_text(
+                    self.launch
+                        .profile()
+                        .map(|profile| profile.name.clone())
+                        .unwrap_or_default(),
+                )
                 .width(120.0)
                 .show_ui(ui, |ui| {
-                    ui.build_menu(&mut self.launch.selected);
+                    for (index, profile) in self.launch.profiles.iter().enumerate() {
+                        ui.selectable_value(&mut self.launch.selected, index, &profile.name)
+                            .on_hover_text(format!("{} on {}", profile.version, profile.platform));
+                    }
                 });
+            if ui
+                .button("↺")
+                .on_hover_text("Look for installs again.")
+                .clicked()
+            {
+                self.launch.discover();
+            }
+        });
+        if let Some(status) = self.crash_recovery.status(&self.settings.crash) {
+            ui.add_enabled_ui(false, |ui| ui.label(status));
+        }
+        if let Some(running) = &self.game_build.running {
+            ui.add_enabled_ui(false, |ui| ui.label(format!("Running build {running}.")));
+        }
+        if let Some(updated_from) = &self.game_build.updated_from {
+            let mut dismiss = false;
+            ui.horizontal(|ui| {
+                ui.colored_label(colours::RED, format!("Game updated since {updated_from}."))
+                    .on_hover_text("Updates can change which network blocking method works.");
+                dismiss = ui.small_button("×").clicked();
+            });
+            if dismiss {
+                self.game_build.dismiss();
+            }
+        }
+        if let Some(status) = self.launcher_cleanup.status() {
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(false, |ui| ui.label(status));
+                if ui
+                    .small_button("×")
+                    .on_hover_text("Leave them running this time.")
+                    .clicked()
+                {
+                    self.launcher_cleanup.cancel("dismissed");
+                }
+            });
+        }
+        if let Some(status) = self.launch.status() {
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(false, |ui| ui.label(status));
+                if !self.launch.is_running() && ui.small_button("×").clicked() {
+                    self.launch.dismiss();
+                }
+            });
+        }
+        let (mut force_close, mut restart) = (false, false);
+        ui.add_enabled_ui(!self.restart.is_running(), |ui| {
+            ui.horizontal(|ui| {
+                let response = ui.confirm_button(
+                    &mut self.confirms.force_close,
+                    &self.settings.confirmations.force_close,
+                    egui::Button::new("Force close game"),
+                );
+                force_close = response.inner;
+                restart = ui
+                    .confirm_button(
+                        &mut self.confirms.restart,
+                        &self.settings.confirmations.restart,
+                        egui::Button::new("Restart game"),
+                    )
+                    .inner;
+            });
         });
-        let force_close_button = ui.button(&self.force_close.button_text);
-        self.force_close
-            .prompt(force_close_button.clicked(), &mut self.system_info);
+        if let Some(hung_for) = self
+            .hang_monitor
+            .hung_for(&self.settings.hang, Instant::now())
+        {
+            ui.horizontal(|ui| {
+                ui.colored_label(
+                    colours::RED,
+                    format!("Not responding for {}s.", hung_for.as_secs()),
+                );
+                if ui
+                    .small_button("×")
+                    .on_hover_text("Ignore it until it responds again.")
+                    .clicked()
+                {
+                    self.hang_monitor.dismiss();
+                }
+            });
+            // the game being stuck is confirmation enough, so these act on the first click
+            ui.add_enabled_ui(!self.restart.is_running(), |ui| {
+                ui.horizontal(|ui| {
+                    force_close |= ui.button("Force close now").clicked();
+                    restart |= ui.button("Restart now").clicked();
+                });
+            });
+        }
+        if force_close {
+            self.hang_monitor.dismiss();
+            self.restart.dismiss();
+            self.force_close.activate(
+                &mut self.system_info,
+                &self.settings.force_close,
+                &self.launch.rockstar_dirs,
+                self.game_watcher.process(),
+            );
+        }
+        if let (true, Some(profile)) = (restart, self.launch.profile().cloned()) {
+            self.hang_monitor.dismiss();
+            self.restart.start(
+                &mut self.force_close,
+                &mut self.system_info,
+                &self.settings.force_close,
+                &self.launch.rockstar_dirs,
+                self.game_watcher.process(),
+                profile,
+            );
+        }
+        if let Some(status) = self.restart.status() {
+            ui.add_enabled_ui(false, |ui| ui.label(status));
+        }
+        self.show_force_close_outcomes(ui);
+        let armed = if self
+            .confirms
+            .restart
+            .is_armed(&self.settings.confirmations.restart)
+        {
+            Some(features::restart::close_settings(
+                &self.settings.force_close,
+            ))
+        } else if self
+            .confirms
+            .force_close
+            .is_armed(&self.settings.confirmations.force_close)
+        {
+            Some(self.settings.force_close.clone())
+        } else {
+            None
+        };
+        if let Some(settings) = armed {
+            ui.add_enabled_ui(false, |ui| {
+                let targets = features::force_close::targets(
+                    &self.system_info,
+                    &settings,
+                    &self.launch.rockstar_dirs,
+                    self.game_watcher.process(),
+                );
+                if targets.is_empty() {
+                    ui.label("Nothing to close.");
+                } else {
+                    for process in targets {
+                        ui.label(format!("{} ({})", process.name().display(), process.pid()));
+                    }
+                }
+            });
+        }
+    }
+
+    fn show_force_close_outcomes(&mut self, ui: &mut egui::Ui) {
+        if self.force_close.is_running() {
+            ui.add_enabled_ui(false, |ui| ui.label("Closing..."));
+        }
+        for outcome in &self.force_close.outcomes {
+            let text = format!(
+                "{} ({}): {}",
+                outcome.name,
+                outcome.pid,
+                match &outcome.result {
+                    Ok(method) => method.to_string(),
+                    Err(why) => why.to_string(),
+                }
+            );
+            match outcome.result {
+                Ok(_) | Err(KillError::AlreadyExited) => {
+                    ui.add_enabled_ui(false, |ui| ui.label(text));
+                }
+                Err(_) => {
+                    ui.colored_label(colours::RED, text);
+                }
+            }
+        }
+        let access_denied = self
+            .force_close
+            .outcomes
+            .iter()
+            .any(|o| o.result == Err(KillError::AccessDenied));
+        if access_denied && !self.flags.elevated {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Access was denied.");
+                if ui.button("Elevate").clicked() {
+                    self.elevate();
+                }
+            });
+        }
     }
 
     fn show_session_section(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
@@ -123,7 +591,7 @@ impl App {
         ui.add_enabled_ui(self.flags.elevated && !self.empty_session.disabled, |ui| {
             ui.horizontal(|ui| {
                 if ui.button("Empty current session").clicked()
-                    && features::empty_session::activate(&mut self.system_info, &self.firewall)
+                    && features::empty_session::activate(&self.game_watcher, &self.firewall)
                         .unwrap()
                 {
                     self.empty_session.interval = Instant::now();
@@ -138,7 +606,7 @@ impl App {
             if self.anti_afk.enabled {
                 ui.add_space(8.0);
                 ui.add_enabled_ui(false, |ui| {
-                    ui.label(if win::is_window_focused(WINDOW_TITLE) {
+                    ui.label(if self.anti_afk.is_game_focused() {
                         "GTA is focused."
                     } else {
                         "GTA is not focused!"
@@ -146,8 +614,66 @@ impl App {
                 });
             }
         });
-        if self.anti_afk.can_activate() && self.anti_afk.should_activate() {
-            self.anti_afk.activate();
+        if self.anti_afk.enabled {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Press now")
+                    .on_hover_text("Press as soon as GTA is focused,\nwithout waiting.")
+                    .clicked()
+                {
+                    self.anti_afk.press_now();
+                }
+                ui.add_space(8.0);
+                ui.add_enabled_ui(false, |ui| {
+                    let next = self.anti_afk.next_press_in(&self.settings.anti_afk);
+                    ui.label(match self.anti_afk.can_activate(&self.settings.anti_afk) {
+                        Err(reason) if next.is_zero() => format!("Waiting: {reason}."),
+                        _ => format!("Next press in {}s.", next.as_millis().div_ceil(1000)),
+                    })
+                });
+            });
+        }
+    }
+
+    fn show_afk_session_section(&mut self, _ctx: &egui::Context, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if self.afk_session.is_running() {
+                if ui.button("Stop AFK session").clicked() {
+                    self.afk_session.stop(&self.anti_afk);
+                }
+            } else if ui
+                .button("Start AFK session")
+                .on_hover_text("Enable anti AFK and keep the PC awake\nuntil the session runs out.")
+                .clicked()
+            {
+                self.afk_session
+                    .start(&self.settings.afk_session, &mut self.anti_afk);
+            }
+            if let Some(remaining) = self.afk_session.remaining() {
+                ui.add_enabled_ui(false, |ui| {
+                    ui.label(format!("{} left", format_minutes(remaining)));
+                });
+            }
+        });
+        if let Some(summary) = &self.afk_session.summary {
+            let mut dismissed = false;
+            ui.horizontal_wrapped(|ui| {
+                ui.add_enabled_ui(false, |ui| {
+                    let ending = summary
+                        .end_action
+                        .map_or_else(|| "stopped".to_owned(), |action| action.to_string());
+                    ui.label(format!(
+                        "Last session: {}, {} presses, {}.",
+                        format_minutes(summary.ran_for),
+                        summary.presses,
+                        ending.to_lowercase()
+                    ));
+                });
+                dismissed = ui.small_button("×").clicked();
+            });
+            if dismissed {
+                self.afk_session.summary = None;
+            }
         }
     }
 
@@ -188,10 +714,7 @@ impl App {
                                         match self.settings.block_method {
                                             BlockMethod::EntireGame => {
                                                 self.game_networking
-                                                    .block_exe(
-                                                        &mut self.system_info,
-                                                        &self.firewall,
-                                                    )
+                                                    .block_exe(&self.game_watcher, &self.firewall)
                                                     .unwrap();
                                             }
                                             BlockMethod::SaveServer => {
@@ -206,7 +729,14 @@ impl App {
                                     }
                                 });
                                 columns[1].vertical_centered_justified(|ui| {
-                                    if ui.button("Unblock").clicked() {
+                                    if ui
+                                        .confirm_button(
+                                            &mut self.confirms.unblock,
+                                            &self.settings.confirmations.unblock,
+                                            egui::Button::new("Unblock"),
+                                        )
+                                        .inner
+                                    {
                                         match self.settings.block_method {
                                             BlockMethod::EntireGame => {
                                                 self.game_networking
@@ -231,8 +761,19 @@ impl App {
     }
 
     fn show_main_stage(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        if let Some(issue) = &self.config_issue {
+            let mut dismiss = false;
+            ui.horizontal_wrapped(|ui| {
+                ui.colored_label(colours::RED, issue.to_string());
+                dismiss = ui.small_button("×").clicked();
+            });
+            if dismiss {
+                self.config_issue = None;
+            }
+        }
         self.show_game_section(ctx, ui);
         self.show_session_section(ctx, ui);
+        self.show_afk_session_section(ctx, ui);
         self.show_network_section(ctx, ui);
     }
 
@@ -252,14 +793,126 @@ impl App {
                 ui.label("Theme");
             });
         });
+        ui.collapsing("Launch profiles", |ui| {
+            self.show_profile_settings(ui);
+        });
         ui.collapsing("Game", |ui| {
             ui.horizontal(|ui| {
-                egui::ComboBox::from_id_salt("Launch version")
-                    .selected_text(self.settings.launch_version.to_string())
+                egui::ComboBox::from_id_salt("Force close targets")
+                    .selected_text(self.settings.force_close.targets.to_string())
                     .show_ui(ui, |ui| {
-                        ui.build_menu(&mut self.settings.launch_version);
+                        ui.build_menu(&mut self.settings.force_close.targets);
+                    })
+                    .response
+                    .on_hover_text(self.settings.force_close.targets.exes().join("\n"));
+                ui.label("Force close");
+            });
+            ui.checkbox(
+                &mut self.settings.force_close.kill_children,
+                "Also close child processes",
+            );
+            ui.checkbox(
+                &mut self.settings.force_close.graceful_first,
+                "Try closing gracefully first",
+            )
+            .on_hover_text("Ask the game to close before terminating it.");
+            let hang = &mut self.settings.hang;
+            ui.checkbox(&mut hang.enabled, "Notice when the game hangs")
+                .on_hover_text(
+                    "Offer to force close or restart it when its window stops responding.",
+                );
+            ui.add_enabled_ui(hang.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut hang.threshold_secs)
+                            .range(3..=120)
+                            .suffix("s"),
+                    );
+                    ui.label("Hang threshold");
+                });
+            });
+            let crash = &mut self.settings.crash;
+            ui.checkbox(&mut crash.auto_relaunch, "Relaunch after a crash")
+                .on_hover_text("Uses whatever was launched last.");
+            ui.add_enabled_ui(crash.auto_relaunch, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut crash.max_retries).range(1..=10));
+                    ui.label("Relaunches in a row");
+                });
+                ui.checkbox(&mut crash.reenable_anti_afk, "Resume anti AFK")
+                    .on_hover_text("Turn anti AFK back on once the game is focused again.");
+            });
+            let cleanup = &mut self.settings.cleanup;
+            ui.checkbox(&mut cleanup.enabled, "Close launchers after the game exits")
+                .on_hover_text("Unless the game is launched again in the meantime.");
+            ui.add_enabled_ui(cleanup.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut cleanup.grace_secs)
+                            .range(0..=600)
+                            .suffix("s"),
+                    );
+                    ui.label("Grace period");
+                });
+                ui.collapsing("Launcher processes", |ui| {
+                    for exe in LEFTOVER_EXES {
+                        let mut enabled = cleanup.exes.iter().any(|name| name == exe);
+                        if ui.checkbox(&mut enabled, exe).changed() {
+                            if enabled {
+                                cleanup.exes.push(exe.to_owned());
+                            } else {
+                                cleanup.exes.retain(|name| name != exe);
+                            }
+                        }
+                    }
+                });
+            });
+        });
+        ui.collapsing("Performance", |ui| self.show_tuning_settings(ui));
+        ui.collapsing("Anti AFK", |ui| self.show_anti_afk_settings(ui));
+        ui.collapsing("AFK session", |ui| {
+            let afk_session = &mut self.settings.afk_session;
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("Session limit")
+                    .selected_text(afk_session.limit.to_string())
+                    .show_ui(ui, |ui| {
+                        ui.build_menu(&mut afk_session.limit);
+                    });
+                ui.label("Limit");
+            });
+            ui.horizontal(|ui| match afk_session.limit {
+                SessionLimit::Duration => {
+                    ui.add(
+                        egui::DragValue::new(&mut afk_session.duration_mins)
+                            .range(1..=24 * 60)
+                            .suffix("m"),
+                    );
+                    ui.label("Duration");
+                }
+                SessionLimit::ClockTime => {
+                    ui.spacing_mut().item_spacing.x = 0.0;
+                    ui.add(
+                        egui::DragValue::new(&mut afk_session.until_hour)
+                            .range(0..=23)
+                            .custom_formatter(|n, _| format!("{n:02}")),
+                    );
+                    ui.label(":");
+                    ui.add(
+                        egui::DragValue::new(&mut afk_session.until_minute)
+                            .range(0..=59)
+                            .custom_formatter(|n, _| format!("{n:02}")),
+                    );
+                    ui.add_space(4.0);
+                    ui.label("Until");
+                }
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("End action")
+                    .selected_text(afk_session.end_action.to_string())
+                    .show_ui(ui, |ui| {
+                        ui.build_menu(&mut afk_session.end_action);
                     });
-                ui.label("Launch version");
+                ui.label("When it ends");
             });
         });
         ui.collapsing("Network", |ui| {
@@ -291,17 +944,456 @@ impl App {
                         }
                     });
                 }
+                if ui
+                    .confirm_button(
+                        &mut self.confirms.remove_rules,
+                        &self.settings.confirmations.remove_rules,
+                        egui::Button::new("Remove all rules"),
+                    )
+                    .inner
+                {
+                    if let Err(why) = self.game_networking.remove_all_rules(&self.firewall) {
+                        log::warn!("Couldn't remove all rules: {why}");
+                    }
+                    if let Err(why) = features::empty_session::deactivate(&self.firewall) {
+                        log::warn!("Couldn't remove empty session rules: {why}");
+                    }
+                }
             })
             .response
             .on_disabled_hover_text("This requires administrator.\nUse the Elevate button.");
         });
+        ui.collapsing("Confirmations", |ui| {
+            let confirmations = &mut self.settings.confirmations;
+            for (setting, name) in [
+                (&mut confirmations.force_close, "Force close"),
+                (&mut confirmations.restart, "Restart"),
+                (&mut confirmations.unblock, "Unblock"),
+                (&mut confirmations.remove_rules, "Remove all rules"),
+                (&mut confirmations.reset_settings, "Reset settings"),
+            ] {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut setting.required, name);
+                    ui.add_enabled(
+                        setting.required,
+                        egui::DragValue::new(&mut setting.armed_secs)
+                            .range(1..=30)
+                            .suffix("s"),
+                    )
+                    .on_hover_text("How long the prompt stays armed for");
+                });
+            }
+        });
         ui.collapsing("Miscellaneous", |ui| {
-            if ui.button("Open storage path").clicked() {
-                open::that_detached(path::APP_STORAGE.as_path()).unwrap();
+            ui.horizontal(|ui| {
+                if ui.button("Open storage path").clicked() {
+                    open::that_detached(path::APP_STORAGE.as_path()).unwrap();
+                }
+                ui.add_enabled_ui(false, |ui| {
+                    let storage_path = format!(
+                        "{} ({})",
+                        path::APP_STORAGE.display(),
+                        storage::LOCATION.source
+                    );
+                    ui.add(egui::Label::new(&storage_path).truncate())
+                        .on_disabled_hover_text(&storage_path);
+                });
+            });
+            if let Some(problem) = &storage::LOCATION.problem {
+                ui.colored_label(colours::RED, problem);
+            }
+            if ui
+                .confirm_button(
+                    &mut self.confirms.reset_settings,
+                    &self.settings.confirmations.reset_settings,
+                    egui::Button::new("Reset settings"),
+                )
+                .inner
+            {
+                self.settings = Settings::default();
+                catppuccin_egui::set_theme(ctx, self.settings.theme.into());
+            }
+        });
+    }
+
+    fn show_tuning_settings(&mut self, ui: &mut egui::Ui) {
+        let tuning = &mut self.settings.tuning;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("Priority")
+                .selected_text(tuning.priority.to_string())
+                .show_ui(ui, |ui| {
+                    ui.build_menu(&mut tuning.priority);
+                });
+            ui.label("Priority");
+        });
+        let cpus = cpu_count().min(64);
+        ui.horizontal(|ui| {
+            let mut all = tuning.affinity == 0;
+            if ui.checkbox(&mut all, "Use all CPUs").changed() {
+                tuning.affinity = if all { 0 } else { 1 };
+            }
+        });
+        if tuning.affinity != 0 {
+            ui.horizontal_wrapped(|ui| {
+                for cpu in 0..cpus {
+                    let bit = 1u64 << cpu;
+                    let mut enabled = tuning.affinity & bit != 0;
+                    if ui.checkbox(&mut enabled, cpu.to_string()).changed() {
+                        tuning.affinity ^= bit;
+                    }
+                }
+            });
+        }
+        ui.checkbox(&mut tuning.auto_apply, "Apply when the game starts");
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.game_watcher.is_game_running(),
+                    egui::Button::new("Apply now"),
+                )
+                .on_disabled_hover_text("The game isn't running.")
+                .clicked()
+            {
+                self.apply_tuning();
+            }
+            if let Some(status) = self.game_tuning.status() {
+                ui.add_enabled_ui(false, |ui| ui.label(status));
             }
         });
     }
 
+    fn show_profile_settings(&mut self, ui: &mut egui::Ui) {
+        let launch = &mut self.launch;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("Edit profile")
+                .selected_text(
+                    launch
+                        .profile()
+                        .map(|profile| profile.name.clone())
+                        .unwrap_or_default(),
+                )
+                .width(120.0)
+                .show_ui(ui, |ui| {
+                    for (index, profile) in launch.profiles.iter().enumerate() {
+                        ui.selectable_value(&mut launch.selected, index, &profile.name);
+                    }
+                });
+            if ui.button("New").clicked() {
+                launch.profiles.push(Profile {
+                    name: format!("Profile {}", launch.profiles.len() + 1),
+                    ..Profile::default()
+                });
+                launch.selected = launch.profiles.len() - 1;
+            }
+            if ui
+                .add_enabled(launch.profiles.len() > 1, egui::Button::new("Delete"))
+                .clicked()
+            {
+                launch.profiles.remove(launch.selected);
+                launch.selected = launch.selected.min(launch.profiles.len() - 1);
+            }
+        });
+        let (profiles, installs) = (&mut launch.profiles, &launch.installs);
+        let Some(profile) = profiles.get_mut(launch.selected) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut profile.name).desired_width(120.0));
+            ui.label("Name");
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("Profile platform")
+                .selected_text(profile.platform.to_string())
+                .show_ui(ui, |ui| {
+                    for platform in Platform::iter() {
+                        let install = installs.iter().find(|install| {
+                            install.platform == platform && install.version == profile.version
+                        });
+                        let response = ui
+                            .add_enabled_ui(install.is_some(), |ui| {
+                                ui.selectable_value(
+                                    &mut profile.platform,
+                                    platform,
+                                    platform.to_string(),
+                                )
+                            })
+                            .inner
+                            .on_disabled_hover_text("Not installed.");
+                        if let Some(install) = install {
+                            response.on_hover_text(format!(
+                                "{}\nBuild {}",
+                                install.path.display(),
+                                install.build_id.as_deref().unwrap_or("unknown"),
+                            ));
+                        }
+                    }
+                });
+            ui.label("Platform");
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("Profile version")
+                .selected_text(profile.version.to_string())
+                .show_ui(ui, |ui| {
+                    ui.build_menu(&mut profile.version);
+                });
+            ui.label("Version");
+        });
+        ui.label("Launch arguments");
+        ui.horizontal_wrapped(|ui| {
+            for flag in LaunchFlag::iter() {
+                let mut enabled = profile.args.flags.contains(&flag);
+                if ui.checkbox(&mut enabled, flag.to_string()).changed() {
+                    if enabled {
+                        profile.args.flags.push(flag);
+                    } else {
+                        profile.args.flags.retain(|f| *f != flag);
+                    }
+                }
+            }
+        });
+        ui.add(egui::TextEdit::singleline(&mut profile.args.extra).hint_text("Other arguments"))
+            .on_hover_text("Separated by spaces, and passed after the ones above.");
+        ui.checkbox(&mut profile.pre_block, "Block network before launching")
+            .on_hover_text(
+                "Uses the block method from the Network settings.\nThis requires administrator.",
+            );
+        ui.label("Once the game has started");
+        for action in PostLaunchAction::iter() {
+            let mut enabled = profile.post_launch.contains(&action);
+            if ui.checkbox(&mut enabled, action.to_string()).changed() {
+                if enabled {
+                    profile.post_launch.push(action);
+                } else {
+                    profile.post_launch.retain(|a| *a != action);
+                }
+            }
+        }
+        if ui
+            .button("Copy shortcut command")
+            .on_hover_text("Copies a command which launches this profile, for use in a shortcut.")
+            .clicked()
+        {
+            match std::env::current_exe() {
+                Ok(exe) => ui.ctx().copy_text(format!(
+                    "\"{}\" --launch \"{}\"",
+                    exe.display(),
+                    profile.name
+                )),
+                Err(why) => log::warn!("Couldn't find our own exe: {why}"),
+            }
+        }
+    }
+
+    fn show_anti_afk_settings(&mut self, ui: &mut egui::Ui) {
+        let anti_afk = &mut self.settings.anti_afk;
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut anti_afk.interval_secs)
+                    .range(10..=900)
+                    .suffix("s"),
+            );
+            ui.label("Interval");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut anti_afk.idle_threshold_secs)
+                    .range(0..=900)
+                    .suffix("s"),
+            )
+            .on_hover_text(
+                "Only press once nobody has touched\nthe keyboard or mouse for this long.",
+            );
+            ui.label("Idle threshold");
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut anti_afk.jitter_secs)
+                    .range(0..=anti_afk.interval_secs / 2)
+                    .suffix("s"),
+            );
+            ui.label("Random jitter (±)");
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("Anti AFK macro")
+                .selected_text(anti_afk.active_macro.as_deref().unwrap_or("Key tap"))
+                .width(120.0)
+                .truncate()
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut anti_afk.active_macro, None, "Key tap");
+                    for named in &anti_afk.macros {
+                        ui.selectable_value(
+                            &mut anti_afk.active_macro,
+                            Some(named.name.clone()),
+                            &named.name,
+                        );
+                    }
+                });
+            ui.label("Macro");
+            if ui.button("+").on_hover_text("Add a new macro").clicked() {
+                let name = (1..)
+                    .map(|i| format!("Macro {i}"))
+                    .find(|name| !anti_afk.macros.iter().any(|m| m.name == *name))
+                    .unwrap();
+                anti_afk.macros.push(NamedMacro {
+                    name: name.clone(),
+                    source: String::from(DEFAULT_MACRO),
+                });
+                anti_afk.active_macro = Some(name);
+            }
+        });
+        let active_index = anti_afk
+            .active_macro
+            .as_ref()
+            .map(|active| anti_afk.macros.iter().position(|m| m.name == *active));
+        if let Some(active_index) = active_index {
+            let Some(index) = active_index else {
+                ui.colored_label(colours::RED, "Selected macro no longer exists.");
+                return;
+            };
+            let named = &mut anti_afk.macros[index];
+            ui.horizontal(|ui| {
+                let name = ui.add(egui::TextEdit::singleline(&mut named.name).desired_width(120.0));
+                if name.changed() {
+                    anti_afk.active_macro = Some(named.name.clone());
+                }
+                ui.label("Name");
+            });
+            ui.add(
+                egui::TextEdit::multiline(&mut named.source)
+                    .code_editor()
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY),
+            )
+            .on_hover_text(
+                "One step per line:\nsend <keys...>\ndown <key>\nup <key>\nwait <ms>\n\
+                 mouse <dx> <dy>\nrepeat <n> ... end",
+            );
+            if let Err(why) = Macro::parse(&named.source) {
+                ui.colored_label(colours::RED, why.to_string());
+            }
+            if ui.button("Remove macro").clicked() {
+                anti_afk.macros.remove(index);
+                anti_afk.active_macro = None;
+            }
+        } else {
+            ui.horizontal(|ui| {
+                let keys = &mut anti_afk.keys;
+                let selected_text = if keys.is_empty() {
+                    "None".to_owned()
+                } else {
+                    keys.iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                egui::ComboBox::from_id_salt("Anti AFK keys")
+                    .selected_text(selected_text)
+                    .width(120.0)
+                    .truncate()
+                    .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                    .show_ui(ui, |ui| {
+                        for key in Key::iter() {
+                            let mut pressed = keys.contains(&key);
+                            if ui.checkbox(&mut pressed, key.to_string()).changed() {
+                                if pressed {
+                                    keys.push(key);
+                                } else {
+                                    keys.retain(|&k| k != key);
+                                }
+                            }
+                        }
+                    });
+                ui.label("Keys");
+            });
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut anti_afk.hold_ms)
+                        .range(0..=2000)
+                        .speed(10)
+                        .suffix("ms"),
+                );
+                ui.label("Hold duration");
+            });
+        }
+    }
+
+    fn show_stats_stage(&self, _ctx: &egui::Context, ui: &mut egui::Ui) {
+        ui.header("Playtime");
+        if let Some((started, version)) = self.game_watcher.current() {
+            let playing_for = started.elapsed().unwrap_or_default();
+            ui.label(format!(
+                "Playing {version} for {}.",
+                format_minutes(playing_for)
+            ));
+        }
+        let today = Day::today();
+        let daily = self.playtime.daily();
+        let weekly = self.playtime.weekly();
+        let total_for = |totals: &[(Day, Duration)], day: Day| {
+            totals
+                .iter()
+                .find(|(total_day, _)| *total_day == day)
+                .map(|(_, total)| *total)
+                .unwrap_or_default()
+        };
+        let all_time = self.playtime.runs.iter().map(Run::duration).sum();
+        ui.label(format!(
+            "Today: {}",
+            format_minutes(total_for(&daily, today))
+        ));
+        ui.label(format!(
+            "This week: {}",
+            format_minutes(total_for(&weekly, today.week_start()))
+        ));
+        ui.label(format!("All time: {}", format_minutes(all_time)));
+        ui.collapsing("Per day", |ui| {
+            for (day, total) in daily.iter().take(HISTORY_DAYS) {
+                ui.label(format!("{day}: {}", format_minutes(*total)));
+            }
+        });
+        ui.collapsing("Per week", |ui| {
+            for (monday, total) in weekly.iter().take(HISTORY_WEEKS) {
+                ui.label(format!("Week of {monday}: {}", format_minutes(*total)));
+            }
+        });
+        ui.collapsing("Runs", |ui| {
+            for run in self.playtime.runs.iter().rev().take(HISTORY_RUNS) {
+                ui.label(format!(
+                    "{} {}: {}, {}",
+                    local_timestamp(run.started),
+                    run.version,
+                    format_minutes(run.duration()),
+                    run.exit,
+                ))
+                .on_hover_text(format!(
+                    "Anti AFK was on for {}.",
+                    format_minutes(run.anti_afk)
+                ));
+            }
+            if self.playtime.runs.len() > HISTORY_RUNS {
+                ui.add_enabled_ui(false, |ui| {
+                    ui.label("Older runs are in the CSV export.");
+                });
+            }
+        });
+        if ui
+            .add_enabled(
+                !self.playtime.runs.is_empty(),
+                egui::Button::new("Export CSV"),
+            )
+            .clicked()
+        {
+            match self.playtime.export_csv() {
+                Ok(path) => {
+                    if let Err(why) = open::that_detached(&path) {
+                        log::warn!("Couldn't open {}: {why}", path.display());
+                    }
+                }
+                Err(why) => log::warn!("Couldn't export playtime: {why}"),
+            }
+        }
+    }
+
     fn show_about_stage(&self, _ctx: &egui::Context, ui: &mut egui::Ui) {
         ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
             ui.horizontal(|ui| {
@@ -343,15 +1435,25 @@ impl App {
 impl Drop for App {
     fn drop(&mut self) {
         // save any persistent state to config file
-        PersistentState {
-            launcher: self.launch.selected,
-            anti_afk_enabled: self.anti_afk.enabled,
-            settings: self.settings.clone(),
+        self.save_config();
+        // the run so far would otherwise never make it into the playtime history
+        if let Some(run) = self.game_watcher.finish() {
+            self.playtime.record(run);
         }
-        .set();
         // make sure we are not network blocking game
         if let Err(why) = features::empty_session::deactivate(&self.firewall) {
             log::error!("couldn't deactivate empty session: {why}");
         }
     }
 }
+
+/// logical cpus, as that's what affinity masks are made of
+pub fn cpu_count() -> usize {
+    std::thread::available_parallelism().map_or(1, std::num::NonZero::get)
+}
+
+/// formats a duration as hours and minutes, like "7h 59m"
+fn format_minutes(duration: Duration) -> String {
+    let minutes = duration.as_secs().div_ceil(60);
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
@@ -1,12 +1,28 @@
+#[cfg(any(test, debug_assertions))]
+use crate::util::{
+    input::{RecordingInput, Win32Input},
+    process_control::{RecordingProcessControl, Win32ProcessControl},
+};
 use crate::{
+    features::{
+        anti_afk::Activity,
+        game_tuning,
+        hang_monitor::{FakeWindow, GameWindow},
+    },
     gui::{
-        app::{App, WINDOW_SIZE},
+        app::{App, WINDOW_SIZE, cpu_count},
         tools,
         ui_ext::UiExt,
     },
-    util::consts::game::{EXE_ENHANCED, EXE_LEGACY},
+    util::{
+        consts::game::{EXE_ENHANCED, EXE_LEGACY},
+        pe_version,
+    },
 };
 use eframe::egui;
+#[cfg(any(test, debug_assertions))]
+use std::time::Duration;
+use std::time::Instant;
 
 impl App {
     fn add_debug_viewport_contents(&mut self, ui: &mut egui::Ui) {
@@ -37,11 +53,66 @@ impl App {
             }
         });
         ui.collapsing("anti afk", |ui| {
+            #[cfg(any(test, debug_assertions))]
+            self.show_anti_afk_dry_run(ui);
             ui.label(format!(
                 "timer: {}",
                 self.anti_afk.interval.elapsed().as_secs()
             ));
-            ui.label(format!("can activate: {}", self.anti_afk.can_activate()));
+            ui.label(format!("idle: {}", self.anti_afk.idle_time().as_secs()));
+            ui.label(format!(
+                "can activate: {:?}",
+                self.anti_afk.can_activate(&self.settings.anti_afk)
+            ));
+            ui.label(format!("presses this session: {}", self.anti_afk.presses));
+            ui.collapsing("history", |ui| {
+                for (time, activity) in self.anti_afk.history.iter().rev() {
+                    let time = humantime::format_rfc3339_seconds(*time);
+                    ui.label(match activity {
+                        Activity::Pressed => format!("{time} pressed"),
+                        Activity::Skipped(reason) => format!("{time} skipped: {reason}"),
+                    });
+                }
+            });
+        });
+        ui.collapsing("hang monitor", |ui| {
+            let fake_hang = ui
+                .checkbox(&mut self.flags.fake_hang, "fake hang")
+                .on_hover_text("pretend the game's window has stopped responding");
+            if fake_hang.changed() {
+                if self.flags.fake_hang {
+                    self.hang_monitor.set_source(Box::new(FakeWindow {
+                        responding: Some(false),
+                    }));
+                } else {
+                    self.hang_monitor.set_source(Box::new(GameWindow));
+                }
+            }
+            ui.label(format!(
+                "hung for: {:?}",
+                self.hang_monitor
+                    .hung_for(&self.settings.hang, Instant::now())
+            ));
+        });
+        ui.collapsing("game tuning", |ui| {
+            #[cfg(any(test, debug_assertions))]
+            self.show_tuning_dry_run(ui);
+            ui.label(format!("game pid: {:?}", self.game_watcher.pid()));
+            ui.label(format!(
+                "changes: {:?}",
+                game_tuning::changes(&self.settings.tuning, cpu_count())
+            ));
+        });
+        ui.collapsing("game build", |ui| {
+            ui.label(format!("running: {:?}", self.game_build.running));
+            ui.label(format!("last seen: {:?}", self.game_build.last_seen));
+            ui.label(format!("updated from: {:?}", self.game_build.updated_from));
+            if ui.button("read installed builds").clicked() {
+                for install in &self.launch.installs {
+                    let exe = install.path.join(install.version.exe());
+                    log::info!("{}: {:?}", exe.display(), pe_version::read(&exe));
+                }
+            }
         });
         ui.collapsing("system info", |ui| {
             if ui.button("refresh").clicked() {
@@ -54,10 +125,46 @@ impl App {
                 .find(|p| p.name() == EXE_ENHANCED || p.name() == EXE_LEGACY)
                 .map_or_else(|| "no pid found!".to_owned(), |p| p.pid().to_string());
             ui.label(format!("gta pid: {pid}"));
+            ui.label(format!("processes: {}", self.system_info.processes().len()));
+            ui.label(format!("watched exe: {:?}", self.game_watcher.exe_path()));
         });
         ui.collapsing("app state", |ui| ui.label(format!("{self:#?}")));
     }
 
+    /// the fake backends only exist in dev builds, and so do these toggles for swapping to them
+    #[cfg(any(test, debug_assertions))]
+    fn show_anti_afk_dry_run(&mut self, ui: &mut egui::Ui) {
+        let dry_run = ui
+            .checkbox(&mut self.flags.dry_run, "dry run")
+            .on_hover_text("record presses in app state instead of sending them");
+        if dry_run.changed() {
+            if self.flags.dry_run {
+                self.anti_afk.set_backend(Box::new(RecordingInput {
+                    focused: true,
+                    idle: Duration::MAX,
+                    ..Default::default()
+                }));
+            } else {
+                self.anti_afk.set_backend(Box::new(Win32Input));
+            }
+        }
+    }
+
+    #[cfg(any(test, debug_assertions))]
+    fn show_tuning_dry_run(&mut self, ui: &mut egui::Ui) {
+        let fake_tuning = ui
+            .checkbox(&mut self.flags.fake_tuning, "dry run")
+            .on_hover_text("record changes in app state instead of making them");
+        if fake_tuning.changed() {
+            if self.flags.fake_tuning {
+                self.game_tuning
+                    .set_control(Box::new(RecordingProcessControl::default()));
+            } else {
+                self.game_tuning.set_control(Box::new(Win32ProcessControl));
+            }
+        }
+    }
+
     pub fn show_debug_viewport(&mut self, ctx: &egui::Context) {
         let main = ctx.input(|i| i.viewport().outer_rect.unwrap_or(egui::Rect::EVERYTHING));
         let builder = egui::ViewportBuilder::default()
@@ -1,4 +1,5 @@
 use crate::{
+    features::playtime::Playtime,
     gui::{
         app::{App, WINDOW_SIZE},
         tools,
@@ -23,8 +24,21 @@ fn app_creator(
     }
     // check if we're elevated. if not, and the user wants an elevated launch - relaunch elevated
     if !app.flags.elevated && app.settings.start_elevated {
+        // exiting this way skips saving on drop, and any migrations should be kept
+        app.save_config();
         win::elevate(win::ElevationExitMethod::Forced);
     }
+    // load the history of game runs, which the stats tab shows
+    app.playtime = Playtime::load();
+    // find where the game is installed, so we only offer platforms which can actually launch it
+    app.launch.discover();
+    // launch a profile straight away if we were asked to, such as by a shortcut
+    if let Some(name) = launch_arg() {
+        match app.launch.find_profile(&name) {
+            Some(index) => app.launch_profile(index),
+            None => log::warn!("There is no launch profile named \"{name}\""),
+        }
+    }
     // refresh system info because it initializes with nothing
     app.system_info.refresh();
     // enable image loading support in egui
@@ -63,6 +77,12 @@ fn app_creator(
     Ok(app)
 }
 
+/// the profile name given with `--launch <name>`
+fn launch_arg() -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--launch");
+    args.nth(1)
+}
+
 pub fn run() {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -1,8 +1,19 @@
-use crate::util::win;
+use crate::{
+    features::afk_macro::NamedMacro,
+    util::{
+        consts::game::{
+            EXE_BATTLEYE, EXE_BATTLEYE_SERVICE, EXE_ENHANCED, EXE_LEGACY, EXE_PLAY, LAUNCHER_EXES,
+            ROCKSTAR_EXES,
+        },
+        input::Key,
+        win,
+    },
+};
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumIter};
+use strum::{Display, EnumIter, IntoEnumIterator};
 
 pub const ROCKSTAR_SAVE_SERVER: &str = "192.81.241.171";
+pub const DEFAULT_MACRO: &str = "send numpad4 numpad6\nwait 200\nmouse 10 0\nwait 100\nmouse -10 0";
 
 #[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
 pub enum Theme {
@@ -37,13 +48,64 @@ impl From<Theme> for catppuccin_egui::Theme {
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Display,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    EnumIter,
+)]
 pub enum LaunchVersion {
     #[default]
     Enhanced,
     Legacy,
 }
 
+impl LaunchVersion {
+    pub const fn exe(self) -> &'static str {
+        match self {
+            Self::Enhanced => EXE_ENHANCED,
+            Self::Legacy => EXE_LEGACY,
+        }
+    }
+}
+
+/// arguments the game understands which are worth having a checkbox for
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum LaunchFlag {
+    #[strum(to_string = "-StraightIntoFreemode")]
+    StraightIntoFreemode,
+    #[strum(to_string = "-goStraightToMP")]
+    GoStraightToMp,
+    #[strum(to_string = "-skipintro")]
+    SkipIntro,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchArgs {
+    pub flags: Vec<LaunchFlag>,
+    pub extra: String,
+}
+
+impl LaunchArgs {
+    /// the checked flags in a stable order, followed by the free text split on whitespace
+    pub fn to_args(&self) -> Vec<String> {
+        LaunchFlag::iter()
+            .filter(|flag| self.flags.contains(flag))
+            .map(|flag| flag.to_string())
+            .chain(self.extra.split_whitespace().map(str::to_owned))
+            .collect()
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
 pub enum BlockMethod {
     #[default]
@@ -53,13 +115,251 @@ pub enum BlockMethod {
     SaveServer,
 }
 
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum ForceCloseTargets {
+    #[default]
+    #[strum(to_string = "Game only")]
+    GameOnly,
+    #[strum(to_string = "Game + launchers")]
+    GameAndLaunchers,
+    #[strum(to_string = "Everything Rockstar")]
+    EverythingRockstar,
+}
+
+impl ForceCloseTargets {
+    pub fn exes(self) -> Vec<&'static str> {
+        let game = [EXE_ENHANCED, EXE_LEGACY];
+        let launchers = [EXE_PLAY, EXE_BATTLEYE].into_iter().chain(LAUNCHER_EXES);
+        match self {
+            Self::GameOnly => game.to_vec(),
+            Self::GameAndLaunchers => game.into_iter().chain(launchers).collect(),
+            Self::EverythingRockstar => game
+                .into_iter()
+                .chain(launchers)
+                .chain(ROCKSTAR_EXES)
+                .chain([EXE_BATTLEYE_SERVICE])
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum Priority {
+    #[default]
+    #[strum(to_string = "Don't change")]
+    Unchanged,
+    #[strum(to_string = "Low")]
+    Idle,
+    #[strum(to_string = "Below normal")]
+    BelowNormal,
+    #[strum(to_string = "Normal")]
+    Normal,
+    #[strum(to_string = "Above normal")]
+    AboveNormal,
+    #[strum(to_string = "High")]
+    High,
+}
+
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum SessionLimit {
+    #[default]
+    #[strum(to_string = "For a duration")]
+    Duration,
+    #[strum(to_string = "Until a time")]
+    ClockTime,
+}
+
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
+pub enum EndAction {
+    #[strum(to_string = "Do nothing")]
+    Nothing,
+    #[default]
+    #[strum(to_string = "Disable anti AFK")]
+    DisableAntiAfk,
+    #[strum(to_string = "Force close game")]
+    ForceCloseGame,
+    #[strum(to_string = "Sleep PC")]
+    Sleep,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AntiAfkSettings {
+    pub keys: Vec<Key>,
+    pub interval_secs: u64,
+    pub idle_threshold_secs: u64,
+    pub hold_ms: u64,
+    pub jitter_secs: u64,
+    pub macros: Vec<NamedMacro>,
+    pub active_macro: Option<String>,
+}
+
+impl Default for AntiAfkSettings {
+    fn default() -> Self {
+        Self {
+            keys: vec![Key::Numpad4, Key::Numpad6],
+            interval_secs: 60,
+            idle_threshold_secs: 60,
+            hold_ms: 0,
+            jitter_secs: 0,
+            macros: vec![NamedMacro {
+                name: "Nudge".to_owned(),
+                source: String::from(DEFAULT_MACRO),
+            }],
+            active_macro: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AfkSessionSettings {
+    pub limit: SessionLimit,
+    pub duration_mins: u64,
+    pub until_hour: u16,
+    pub until_minute: u16,
+    pub end_action: EndAction,
+}
+
+impl Default for AfkSessionSettings {
+    fn default() -> Self {
+        Self {
+            limit: SessionLimit::default(),
+            duration_mins: 8 * 60,
+            until_hour: 8,
+            until_minute: 0,
+            end_action: EndAction::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CrashSettings {
+    pub auto_relaunch: bool,
+    /// relaunches in a row before giving up, reset by a run which didn't crash quickly
+    pub max_retries: u32,
+    pub reenable_anti_afk: bool,
+}
+
+impl Default for CrashSettings {
+    fn default() -> Self {
+        Self {
+            auto_relaunch: false,
+            max_retries: 3,
+            reenable_anti_afk: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HangSettings {
+    pub enabled: bool,
+    pub threshold_secs: u64,
+}
+
+impl Default for HangSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_secs: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanupSettings {
+    pub enabled: bool,
+    /// how long after the game exits to wait before closing anything
+    pub grace_secs: u64,
+    pub exes: Vec<String>,
+}
+
+impl Default for CleanupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            grace_secs: 60,
+            exes: LAUNCHER_EXES
+                .into_iter()
+                .chain(ROCKSTAR_EXES)
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TuningSettings {
+    /// apply whenever the game is found running, including after a restart
+    pub auto_apply: bool,
+    pub priority: Priority,
+    /// one bit per logical cpu, where no bits means leaving the affinity alone
+    pub affinity: u64,
+}
+
+impl Default for TuningSettings {
+    fn default() -> Self {
+        Self {
+            auto_apply: true,
+            priority: Priority::default(),
+            affinity: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForceCloseSettings {
+    pub targets: ForceCloseTargets,
+    pub kill_children: bool,
+    pub graceful_first: bool,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmSetting {
+    pub required: bool,
+    pub armed_secs: u64,
+}
+
+impl Default for ConfirmSetting {
+    fn default() -> Self {
+        Self {
+            required: true,
+            armed_secs: 3,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationSettings {
+    pub force_close: ConfirmSetting,
+    pub restart: ConfirmSetting,
+    pub unblock: ConfirmSetting,
+    pub remove_rules: ConfirmSetting,
+    pub reset_settings: ConfirmSetting,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub start_elevated: bool,
     pub theme: Theme,
-    pub launch_version: LaunchVersion,
+    pub force_close: ForceCloseSettings,
+    pub crash: CrashSettings,
+    pub hang: HangSettings,
+    pub cleanup: CleanupSettings,
+    pub tuning: TuningSettings,
     pub save_server_ip: String,
     pub block_method: BlockMethod,
+    pub anti_afk: AntiAfkSettings,
+    pub afk_session: AfkSessionSettings,
+    pub confirmations: ConfirmationSettings,
 }
 
 impl Default for Settings {
@@ -67,9 +367,16 @@ impl Default for Settings {
         Self {
             start_elevated: false,
             theme: Theme::default(),
-            launch_version: LaunchVersion::default(),
+            force_close: ForceCloseSettings::default(),
+            crash: CrashSettings::default(),
+            hang: HangSettings::default(),
+            cleanup: CleanupSettings::default(),
+            tuning: TuningSettings::default(),
             block_method: BlockMethod::default(),
             save_server_ip: String::from(ROCKSTAR_SAVE_SERVER),
+            anti_afk: AntiAfkSettings::default(),
+            afk_session: AfkSessionSettings::default(),
+            confirmations: ConfirmationSettings::default(),
         }
     }
 }
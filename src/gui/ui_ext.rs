@@ -1,4 +1,19 @@
+use crate::gui::settings::ConfirmSetting;
 use eframe::egui;
+use std::time::{Duration, Instant};
+
+/// the state of a button which needs a second click to confirm its action
+#[derive(Debug, Default)]
+pub struct Confirm {
+    armed_at: Option<Instant>,
+}
+
+impl Confirm {
+    pub fn is_armed(&self, setting: &ConfirmSetting) -> bool {
+        self.armed_at
+            .is_some_and(|armed_at| armed_at.elapsed() < Duration::from_secs(setting.armed_secs))
+    }
+}
 
 pub trait UiExt {
     fn header(&mut self, text: &str);
@@ -6,6 +21,12 @@ pub trait UiExt {
     where
         E: strum::IntoEnumIterator + std::fmt::Display + std::cmp::PartialEq + Copy;
     fn create_indicator_dot(&mut self, colour: impl Into<egui::Color32>) -> egui::Response;
+    fn confirm_button(
+        &mut self,
+        confirm: &mut Confirm,
+        setting: &ConfirmSetting,
+        button: egui::Button,
+    ) -> egui::InnerResponse<bool>;
 }
 
 impl UiExt for egui::Ui {
@@ -35,4 +56,33 @@ impl UiExt for egui::Ui {
                 .tint(colour),
         )
     }
+
+    /// a button which only reports being confirmed on a second click while armed,
+    /// or on the first click if the setting doesn't require confirmation
+    fn confirm_button(
+        &mut self,
+        confirm: &mut Confirm,
+        setting: &ConfirmSetting,
+        button: egui::Button,
+    ) -> egui::InnerResponse<bool> {
+        let armed = confirm.is_armed(setting);
+        let button = if armed {
+            egui::Button::new("Are you sure?")
+        } else {
+            button
+        };
+        let response = self.add(button);
+        let mut confirmed = false;
+        if response.clicked() {
+            if armed || !setting.required {
+                confirmed = true;
+                confirm.armed_at = None;
+            } else {
+                confirm.armed_at = Some(Instant::now());
+            }
+        } else if !armed {
+            confirm.armed_at = None;
+        }
+        egui::InnerResponse::new(confirmed, response)
+    }
 }
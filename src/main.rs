@@ -6,9 +6,11 @@ mod util;
 
 use std::fmt::Write;
 
+/// storage is created when it's first used, which the logger already did, so this only reports
+/// on how that went
 fn init_storage() {
-    if !util::consts::path::APP_STORAGE.exists() {
-        std::fs::create_dir_all(util::consts::path::APP_STORAGE.as_path()).unwrap();
+    if let Some(problem) = &util::storage::LOCATION.problem {
+        log::warn!("{problem}");
     }
 }
 
@@ -22,8 +24,8 @@ fn panic_hook(panic_info: &std::panic::PanicHookInfo<'_>) {
 }
 
 fn main() {
-    init_storage();
     util::logging::Logger::init(log::LevelFilter::Info);
+    init_storage();
     std::panic::set_hook(Box::new(panic_hook));
     gui::run::run();
 }
@@ -1,7 +1,12 @@
 pub mod consts;
 pub mod countdown;
 pub mod firewall;
+pub mod input;
+pub mod installs;
 pub mod logging;
+pub mod pe_version;
 pub mod persistent_state;
+pub mod process_control;
+pub mod storage;
 pub mod system_info;
 pub mod win;
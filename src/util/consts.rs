@@ -1,17 +1,67 @@
 pub mod path {
-    use std::{env, path::PathBuf, sync::LazyLock};
-    pub static APP_STORAGE: LazyLock<PathBuf> = LazyLock::new(|| {
-        PathBuf::from(env::var("LOCALAPPDATA").unwrap_or_else(|_| String::from(".")))
-            .join("GTA Tools")
-    });
+    use crate::util::storage;
+    use std::{path::PathBuf, sync::LazyLock};
+    pub static APP_STORAGE: LazyLock<PathBuf> = LazyLock::new(|| storage::LOCATION.path.clone());
     pub static APP_CONFIG: LazyLock<PathBuf> = LazyLock::new(|| APP_STORAGE.join("config.json"));
+    pub static APP_CONFIG_UNREADABLE: LazyLock<PathBuf> =
+        LazyLock::new(|| APP_STORAGE.join("config.unreadable.json"));
+    pub static APP_CONFIG_TEMP: LazyLock<PathBuf> =
+        LazyLock::new(|| APP_STORAGE.join("config.json.tmp"));
+    /// the nth newest backup of the config, counting from 1
+    pub fn app_config_backup(n: usize) -> PathBuf {
+        APP_STORAGE.join(format!("config.backup.{n}.json"))
+    }
+    /// a config from a newer version of the app, kept before we overwrite it with our own layout
+    pub fn app_config_newer(version: u64) -> PathBuf {
+        APP_STORAGE.join(format!("config.v{version}.json"))
+    }
+    /// what we last wrote to an epic install's commandline.txt, so that we know it's ours
+    pub fn app_epic_commandline(version: impl std::fmt::Display) -> PathBuf {
+        APP_STORAGE.join(format!("epic-commandline.{version}.txt"))
+    }
     pub static APP_LOG: LazyLock<PathBuf> = LazyLock::new(|| APP_STORAGE.join("gta-tools.log"));
+    pub static APP_PLAYTIME: LazyLock<PathBuf> =
+        LazyLock::new(|| APP_STORAGE.join("playtime.json"));
+    pub static APP_PLAYTIME_CSV: LazyLock<PathBuf> =
+        LazyLock::new(|| APP_STORAGE.join("playtime.csv"));
 }
 
 pub mod game {
     pub const EXE_ENHANCED: &str = "GTA5_Enhanced.exe";
     pub const EXE_LEGACY: &str = "GTA5.exe";
+    pub const EXE_BATTLEYE: &str = "GTA5_BE.exe";
+    pub const EXE_PLAY: &str = "PlayGTAV.exe";
+    pub const LAUNCHER_EXES: [&str; 2] = ["Launcher.exe", "LauncherPatcher.exe"];
+    pub const ROCKSTAR_EXES: [&str; 3] = [
+        "RockstarService.exe",
+        "SocialClubHelper.exe",
+        "RockstarErrorHandler.exe",
+    ];
+    /// battleye's service is shared with other games, so it's only ever closed on request
+    pub const EXE_BATTLEYE_SERVICE: &str = "BEService.exe";
+    /// everything that may be left running once the game has exited
+    pub const LEFTOVER_EXES: [&str; 7] = [
+        EXE_PLAY,
+        EXE_BATTLEYE,
+        LAUNCHER_EXES[0],
+        LAUNCHER_EXES[1],
+        ROCKSTAR_EXES[0],
+        ROCKSTAR_EXES[1],
+        ROCKSTAR_EXES[2],
+    ];
     pub const WINDOW_TITLE: &str = "Grand Theft Auto V";
+    pub const EXE_STEAM: &str = "steam.exe";
+    pub const EXE_EPIC: &str = "EpicGamesLauncher.exe";
+    pub const EXE_WER: &str = "WerFault.exe";
+    pub const STEAM_APP_ENHANCED: &str = "3240220";
+    pub const STEAM_APP_LEGACY: &str = "271590";
+    pub const EPIC_APP_ENHANCED: &str = "8769e24080ea413b8ebca3f1b8c50951";
+    pub const EPIC_APP_LEGACY: &str = "9d2d0eb64d5c44529cece33fe2a46482";
+    pub const ROCKSTAR_KEY_ENHANCED: &str = r"SOFTWARE\WOW6432Node\Rockstar Games\GTAV Enhanced";
+    pub const ROCKSTAR_KEY_LEGACY: &str = r"SOFTWARE\WOW6432Node\Rockstar Games\Grand Theft Auto V";
+    pub const ROCKSTAR_KEY_LAUNCHER: &str = r"SOFTWARE\WOW6432Node\Rockstar Games\Launcher";
+    pub const ROCKSTAR_KEY_SOCIAL_CLUB: &str =
+        r"SOFTWARE\WOW6432Node\Rockstar Games\Rockstar Games Social Club";
 }
 
 pub mod colours {
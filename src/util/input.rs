@@ -0,0 +1,248 @@
+use crate::util::win;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use strum::{Display, EnumIter, EnumString};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    INPUT, INPUT_KEYBOARD, INPUT_MOUSE, KEYBD_EVENT_FLAGS, KEYBDINPUT, KEYEVENTF_KEYUP,
+    MAPVK_VK_TO_VSC, MOUSEEVENTF_MOVE, MOUSEINPUT, MapVirtualKeyW, SendInput, VIRTUAL_KEY, VK_A,
+    VK_D, VK_DOWN, VK_E, VK_LCONTROL, VK_LEFT, VK_LSHIFT, VK_NUMPAD0, VK_NUMPAD1, VK_NUMPAD2,
+    VK_NUMPAD3, VK_NUMPAD4, VK_NUMPAD5, VK_NUMPAD6, VK_NUMPAD7, VK_NUMPAD8, VK_NUMPAD9, VK_Q,
+    VK_RIGHT, VK_S, VK_SPACE, VK_TAB, VK_UP, VK_W, VK_X, VK_Z,
+};
+
+#[derive(
+    Clone, Copy, Debug, Display, PartialEq, Eq, Serialize, Deserialize, EnumIter, EnumString,
+)]
+#[strum(ascii_case_insensitive)]
+pub enum Key {
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    W,
+    A,
+    S,
+    D,
+    Q,
+    E,
+    X,
+    Z,
+    Space,
+    #[strum(to_string = "Left Shift", serialize = "LShift")]
+    LShift,
+    #[strum(to_string = "Left Ctrl", serialize = "LControl")]
+    LControl,
+    Tab,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl From<Key> for VIRTUAL_KEY {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Numpad0 => VK_NUMPAD0,
+            Key::Numpad1 => VK_NUMPAD1,
+            Key::Numpad2 => VK_NUMPAD2,
+            Key::Numpad3 => VK_NUMPAD3,
+            Key::Numpad4 => VK_NUMPAD4,
+            Key::Numpad5 => VK_NUMPAD5,
+            Key::Numpad6 => VK_NUMPAD6,
+            Key::Numpad7 => VK_NUMPAD7,
+            Key::Numpad8 => VK_NUMPAD8,
+            Key::Numpad9 => VK_NUMPAD9,
+            Key::W => VK_W,
+            Key::A => VK_A,
+            Key::S => VK_S,
+            Key::D => VK_D,
+            Key::Q => VK_Q,
+            Key::E => VK_E,
+            Key::X => VK_X,
+            Key::Z => VK_Z,
+            Key::Space => VK_SPACE,
+            Key::LShift => VK_LSHIFT,
+            Key::LControl => VK_LCONTROL,
+            Key::Tab => VK_TAB,
+            Key::Up => VK_UP,
+            Key::Down => VK_DOWN,
+            Key::Left => VK_LEFT,
+            Key::Right => VK_RIGHT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyState {
+    Down,
+    Up,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    Key { key: Key, state: KeyState },
+    MouseMove { dx: i32, dy: i32 },
+}
+
+impl InputEvent {
+    pub const fn key_down(key: Key) -> Self {
+        Self::Key {
+            key,
+            state: KeyState::Down,
+        }
+    }
+
+    pub const fn key_up(key: Key) -> Self {
+        Self::Key {
+            key,
+            state: KeyState::Up,
+        }
+    }
+}
+
+/// everything anti afk needs from the operating system, so that it can be swapped out
+pub trait InputBackend: std::fmt::Debug {
+    fn send(&mut self, events: &[InputEvent]);
+    fn is_window_focused(&self, title: &str) -> bool;
+    fn is_cursor_visible(&self) -> bool;
+    fn is_any_key_pressed(&self, keys: &[Key]) -> bool;
+    /// time since the last input seen by the system, including any we injected ourselves
+    fn idle_time(&self) -> Duration;
+}
+
+#[derive(Debug, Default)]
+pub struct Win32Input;
+
+impl InputBackend for Win32Input {
+    fn send(&mut self, events: &[InputEvent]) {
+        let inputs = events.iter().map(to_win32_input).collect::<Vec<_>>();
+        unsafe { SendInput(&inputs, size_of::<INPUT>() as i32) };
+    }
+
+    fn is_window_focused(&self, title: &str) -> bool {
+        win::is_window_focused(title)
+    }
+
+    fn is_cursor_visible(&self) -> bool {
+        win::is_cursor_visible()
+    }
+
+    fn is_any_key_pressed(&self, keys: &[Key]) -> bool {
+        let keys = keys
+            .iter()
+            .copied()
+            .map(VIRTUAL_KEY::from)
+            .collect::<Vec<_>>();
+        win::is_any_key_pressed(&keys)
+    }
+
+    fn idle_time(&self) -> Duration {
+        win::idle_time()
+    }
+}
+
+/// a fake backend which reports whatever state it was scripted with,
+/// and records the events sent to it instead of injecting them
+#[cfg(any(test, debug_assertions))]
+#[derive(Debug, Default)]
+pub struct RecordingInput {
+    pub focused: bool,
+    pub cursor_visible: bool,
+    pub pressed: Vec<Key>,
+    pub idle: Duration,
+    pub recorded: Vec<(std::time::Instant, InputEvent)>,
+}
+
+#[cfg(any(test, debug_assertions))]
+impl InputBackend for RecordingInput {
+    fn send(&mut self, events: &[InputEvent]) {
+        let now = std::time::Instant::now();
+        self.recorded
+            .extend(events.iter().map(|&event| (now, event)));
+    }
+
+    fn is_window_focused(&self, _title: &str) -> bool {
+        self.focused
+    }
+
+    fn is_cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    fn is_any_key_pressed(&self, keys: &[Key]) -> bool {
+        keys.iter().any(|key| self.pressed.contains(key))
+    }
+
+    fn idle_time(&self) -> Duration {
+        self.idle
+    }
+}
+
+/// lets a test keep hold of a fake backend once it's been handed over
+#[cfg(test)]
+impl<T: InputBackend> InputBackend for std::rc::Rc<std::cell::RefCell<T>> {
+    fn send(&mut self, events: &[InputEvent]) {
+        self.borrow_mut().send(events);
+    }
+
+    fn is_window_focused(&self, title: &str) -> bool {
+        self.borrow().is_window_focused(title)
+    }
+
+    fn is_cursor_visible(&self) -> bool {
+        self.borrow().is_cursor_visible()
+    }
+
+    fn is_any_key_pressed(&self, keys: &[Key]) -> bool {
+        self.borrow().is_any_key_pressed(keys)
+    }
+
+    fn idle_time(&self) -> Duration {
+        self.borrow().idle_time()
+    }
+}
+
+fn to_win32_input(event: &InputEvent) -> INPUT {
+    match *event {
+        InputEvent::Key { key, state } => {
+            let vk_code = VIRTUAL_KEY::from(key);
+            let scan_code = unsafe { MapVirtualKeyW(u32::from(vk_code.0), MAPVK_VK_TO_VSC) } as u16;
+            let mut input = INPUT {
+                r#type: INPUT_KEYBOARD,
+                ..Default::default()
+            };
+            input.Anonymous.ki = KEYBDINPUT {
+                wVk: vk_code,
+                wScan: scan_code,
+                dwFlags: match state {
+                    KeyState::Down => KEYBD_EVENT_FLAGS(0),
+                    KeyState::Up => KEYEVENTF_KEYUP,
+                },
+                time: 0,
+                dwExtraInfo: 0,
+            };
+            input
+        }
+        InputEvent::MouseMove { dx, dy } => {
+            let mut input = INPUT {
+                r#type: INPUT_MOUSE,
+                ..Default::default()
+            };
+            input.Anonymous.mi = MOUSEINPUT {
+                dx,
+                dy,
+                mouseData: 0,
+                dwFlags: MOUSEEVENTF_MOVE,
+                time: 0,
+                dwExtraInfo: 0,
+            };
+            input
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{
+    features::launch::Platform,
+    gui::settings::LaunchVersion,
+    util::consts::game::{
+        EPIC_APP_ENHANCED, EPIC_APP_LEGACY, EXE_PLAY, ROCKSTAR_KEY_ENHANCED, ROCKSTAR_KEY_LAUNCHER,
+        ROCKSTAR_KEY_LEGACY, ROCKSTAR_KEY_SOCIAL_CLUB, STEAM_APP_ENHANCED, STEAM_APP_LEGACY,
+    },
+};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use strum::IntoEnumIterator;
+use winreg::{
+    RegKey,
+    enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE},
+};
+
+/// steam's StateFlags bit for a fully installed app
+const STEAM_FULLY_INSTALLED: u32 = 4;
+
+/// a copy of the game found on disk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Install {
+    pub platform: Platform,
+    pub version: LaunchVersion,
+    pub path: PathBuf,
+    pub build_id: Option<String>,
+}
+
+/// looks for every installed copy of the game across all platforms
+pub fn discover() -> Vec<Install> {
+    let mut installs = Vec::new();
+    for platform in Platform::iter() {
+        let found = match platform {
+            Platform::Steam => discover_steam(),
+            Platform::Rockstar => Ok(LaunchVersion::iter().filter_map(rockstar).collect()),
+            Platform::Epic => discover_epic(),
+        };
+        match found {
+            Ok(found) => installs.extend(found),
+            Err(why) => log::info!("Couldn't look for {platform} installs: {why}"),
+        }
+    }
+    installs
+}
+
+fn discover_steam() -> Result<Vec<Install>> {
+    let steam: String = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(r"Software\Valve\Steam")
+        .and_then(|key| key.get_value("SteamPath"))
+        .context("Steam isn't installed")?;
+    let library_folders = Path::new(&steam).join(r"steamapps\libraryfolders.vdf");
+    let library_folders = fs::read_to_string(&library_folders)
+        .with_context(|| format!("couldn't read {}", library_folders.display()))?;
+    let mut installs = Vec::new();
+    for library in parse_library_folders(&library_folders)? {
+        let steamapps = library.join("steamapps");
+        for version in LaunchVersion::iter() {
+            let app_id = match version {
+                LaunchVersion::Enhanced => STEAM_APP_ENHANCED,
+                LaunchVersion::Legacy => STEAM_APP_LEGACY,
+            };
+            let manifest_path = steamapps.join(format!("appmanifest_{app_id}.acf"));
+            let Ok(manifest) = fs::read_to_string(&manifest_path) else {
+                continue;
+            };
+            let manifest = match AppManifest::parse(&manifest) {
+                Ok(manifest) => manifest,
+                Err(why) => {
+                    log::warn!("Couldn't parse {}: {why}", manifest_path.display());
+                    continue;
+                }
+            };
+            let path = steamapps.join("common").join(&manifest.install_dir);
+            if manifest.is_installed() && path.is_dir() {
+                installs.push(Install {
+                    platform: Platform::Steam,
+                    version,
+                    path,
+                    build_id: Some(manifest.build_id),
+                });
+            }
+        }
+    }
+    Ok(installs)
+}
+
+fn discover_epic() -> Result<Vec<Install>> {
+    let manifests =
+        PathBuf::from(env::var("PROGRAMDATA").unwrap_or_else(|_| String::from(r"C:\ProgramData")))
+            .join(r"Epic\EpicGamesLauncher\Data\Manifests");
+    let entries = fs::read_dir(&manifests).context("Epic Games Launcher isn't installed")?;
+    let mut installs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "item") {
+            continue;
+        }
+        let Ok(item) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let item = match EpicItem::parse(&item) {
+            Ok(item) => item,
+            Err(why) => {
+                log::warn!("Couldn't parse {}: {why}", path.display());
+                continue;
+            }
+        };
+        let version = match item.app_name.as_str() {
+            EPIC_APP_ENHANCED => LaunchVersion::Enhanced,
+            EPIC_APP_LEGACY => LaunchVersion::Legacy,
+            _ => continue,
+        };
+        if !item.incomplete && item.install_location.is_dir() {
+            installs.push(Install {
+                platform: Platform::Epic,
+                version,
+                path: item.install_location,
+                build_id: Some(item.app_version_string),
+            });
+        }
+    }
+    Ok(installs)
+}
+
+/// the epic games launcher install of the given version, if there is one
+pub fn epic(version: LaunchVersion) -> Option<Install> {
+    discover_epic()
+        .ok()?
+        .into_iter()
+        .find(|install| install.version == version)
+}
+
+/// the rockstar games launcher install of the given version, if there is one
+pub fn rockstar(version: LaunchVersion) -> Option<Install> {
+    let key = match version {
+        LaunchVersion::Enhanced => ROCKSTAR_KEY_ENHANCED,
+        LaunchVersion::Legacy => ROCKSTAR_KEY_LEGACY,
+    };
+    let path = install_folder(key)?;
+    path.join(EXE_PLAY).is_file().then_some(Install {
+        platform: Platform::Rockstar,
+        version,
+        path,
+        build_id: None,
+    })
+}
+
+/// every folder the game, rockstar's launcher and its services live in, which is what tells
+/// their processes apart from unrelated ones that happen to share a name like `Launcher.exe`
+pub fn rockstar_dirs(installs: &[Install]) -> Vec<PathBuf> {
+    installs
+        .iter()
+        .map(|install| install.path.clone())
+        .chain(
+            [ROCKSTAR_KEY_LAUNCHER, ROCKSTAR_KEY_SOCIAL_CLUB]
+                .into_iter()
+                .filter_map(install_folder),
+        )
+        .collect()
+}
+
+fn install_folder(key: &str) -> Option<PathBuf> {
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey(key)
+        .and_then(|key| key.get_value::<String, _>("InstallFolder"))
+        .ok()
+        .map(PathBuf::from)
+}
+
+/// the subset of valve's KeyValues text format used by steam's library files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Vdf {
+    Value(String),
+    Object(Vec<(String, Vdf)>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    String(String),
+}
+
+impl Vdf {
+    /// parses a whole file, which is an object without the surrounding braces
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut tokens = tokenize(source)?.into_iter();
+        parse_object(&mut tokens, false).map(Self::Object)
+    }
+
+    /// the value of a key in an object, which like steam, we match case-insensitively
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        self.entries()
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn entries(&self) -> &[(String, Self)] {
+        match self {
+            Self::Value(_) => &[],
+            Self::Object(entries) => entries,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Object(_) => None,
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(escaped) => string.push(escaped),
+                            None => bail!("unterminated string"),
+                        },
+                        Some(c) => string.push(c),
+                        None => bail!("unterminated string"),
+                    }
+                }
+                tokens.push(Token::String(string));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                // unquoted strings run until whitespace or the next special character
+                let mut string = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | '}' | '"') {
+                        break;
+                    }
+                    string.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::String(string));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_object(
+    tokens: &mut impl Iterator<Item = Token>,
+    nested: bool,
+) -> Result<Vec<(String, Vdf)>> {
+    let mut entries = Vec::new();
+    loop {
+        let key = match tokens.next() {
+            Some(Token::String(key)) => key,
+            Some(Token::Close) if nested => return Ok(entries),
+            None if !nested => return Ok(entries),
+            Some(Token::Close) => bail!("unexpected closing brace"),
+            Some(Token::Open) => bail!("expected a key, found an opening brace"),
+            None => bail!("missing closing brace"),
+        };
+        let value = match tokens.next() {
+            Some(Token::String(value)) => Vdf::Value(value),
+            Some(Token::Open) => Vdf::Object(parse_object(tokens, true)?),
+            _ => bail!("\"{key}\" has no value"),
+        };
+        entries.push((key, value));
+    }
+}
+
+/// the library folders listed in steam's libraryfolders.vdf
+pub fn parse_library_folders(source: &str) -> Result<Vec<PathBuf>> {
+    let vdf = Vdf::parse(source)?;
+    let folders = vdf
+        .get("libraryfolders")
+        .context("missing \"libraryfolders\"")?;
+    let mut paths = Vec::new();
+    for (index, folder) in folders.entries() {
+        // other keys such as "contentstatsid" are mixed in with the numbered folders
+        if !index.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        // older files have the path directly, newer ones have an object with a path key
+        let path = match folder {
+            Vdf::Value(path) => Some(path.as_str()),
+            Vdf::Object(_) => folder.get("path").and_then(Vdf::as_str),
+        };
+        let Some(path) = path.map(PathBuf::from) else {
+            continue;
+        };
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+/// the parts of a steam appmanifest_*.acf that we care about
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AppManifest {
+    pub app_id: String,
+    pub install_dir: String,
+    pub build_id: String,
+    pub state_flags: u32,
+}
+
+impl AppManifest {
+    pub fn parse(source: &str) -> Result<Self> {
+        let vdf = Vdf::parse(source)?;
+        let app_state = vdf.get("AppState").context("missing \"AppState\"")?;
+        let field = |key: &str| {
+            app_state
+                .get(key)
+                .and_then(Vdf::as_str)
+                .with_context(|| format!("missing \"{key}\""))
+        };
+        Ok(Self {
+            app_id: field("appid")?.to_owned(),
+            install_dir: field("installdir")?.to_owned(),
+            build_id: field("buildid")?.to_owned(),
+            state_flags: field("StateFlags")?
+                .parse()
+                .context("\"StateFlags\" is not a number")?,
+        })
+    }
+
+    pub const fn is_installed(&self) -> bool {
+        self.state_flags & STEAM_FULLY_INSTALLED != 0
+    }
+}
+
+/// the parts of an epic games launcher .item manifest that we care about
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EpicItem {
+    pub app_name: String,
+    pub install_location: PathBuf,
+    #[serde(default)]
+    pub app_version_string: String,
+    #[serde(rename = "bIsIncompleteInstall", default)]
+    pub incomplete: bool,
+}
+
+impl EpicItem {
+    pub fn parse(source: &str) -> Result<Self> {
+        serde_json::from_str(source).context("invalid manifest")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY_FOLDERS: &str = include_str!("../../tests/fixtures/installs/libraryfolders.vdf");
+    const LIBRARY_FOLDERS_OLD: &str =
+        include_str!("../../tests/fixtures/installs/libraryfolders_old.vdf");
+    const APP_MANIFEST: &str =
+        include_str!("../../tests/fixtures/installs/appmanifest_3240220.acf");
+    const EPIC_ITEM: &str = include_str!("../../tests/fixtures/installs/gtav.item");
+
+    fn error<T: std::fmt::Debug>(result: Result<T>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn vdf() {
+        let vdf = Vdf::parse("\"a\" { B \"1\" // comment\n \"c\" { } \"d\" \"x\\\"y\\\\z\\n\" }")
+            .unwrap();
+        let a = vdf.get("A").unwrap();
+        assert_eq!(a.get("b").and_then(Vdf::as_str), Some("1"));
+        assert_eq!(a.get("c"), Some(&Vdf::Object(Vec::new())));
+        assert_eq!(a.get("d").and_then(Vdf::as_str), Some("x\"y\\z\n"));
+        assert_eq!(a.get("missing"), None);
+        assert_eq!(a.get("b").unwrap().entries(), []);
+
+        assert_eq!(error(Vdf::parse("\"a\" \"b")), "unterminated string");
+        assert_eq!(error(Vdf::parse("\"a\" {")), "missing closing brace");
+        assert_eq!(error(Vdf::parse("\"a\" }")), "\"a\" has no value");
+        assert_eq!(error(Vdf::parse("}")), "unexpected closing brace");
+        assert_eq!(
+            error(Vdf::parse("{")),
+            "expected a key, found an opening brace"
+        );
+    }
+
+    #[test]
+    fn library_folders() {
+        assert_eq!(
+            parse_library_folders(LIBRARY_FOLDERS).unwrap(),
+            [
+                PathBuf::from(r"C:\Program Files (x86)\Steam"),
+                PathBuf::from(r"D:\SteamLibrary"),
+            ]
+        );
+        assert_eq!(
+            parse_library_folders(LIBRARY_FOLDERS_OLD).unwrap(),
+            [
+                PathBuf::from(r"D:\SteamLibrary"),
+                PathBuf::from(r"E:\Games\Steam"),
+            ]
+        );
+        assert_eq!(
+            error(parse_library_folders("\"apps\" {}")),
+            "missing \"libraryfolders\""
+        );
+    }
+
+    #[test]
+    fn app_manifest() {
+        let manifest = AppManifest::parse(APP_MANIFEST).unwrap();
+        assert_eq!(
+            manifest,
+            AppManifest {
+                app_id: String::from("3240220"),
+                install_dir: String::from("Grand Theft Auto V Enhanced"),
+                build_id: String::from("17563232"),
+                state_flags: 4,
+            }
+        );
+        assert!(manifest.is_installed());
+
+        let updating = APP_MANIFEST.replace("\"StateFlags\"\t\t\"4\"", "\"StateFlags\"\t\t\"6\"");
+        assert!(AppManifest::parse(&updating).unwrap().is_installed());
+        let downloading =
+            APP_MANIFEST.replace("\"StateFlags\"\t\t\"4\"", "\"StateFlags\"\t\t\"1026\"");
+        assert!(!AppManifest::parse(&downloading).unwrap().is_installed());
+
+        let no_build = APP_MANIFEST.replace("\"buildid\"", "\"oldbuildid\"");
+        assert_eq!(error(AppManifest::parse(&no_build)), "missing \"buildid\"");
+        let bad_flags = APP_MANIFEST.replace("\"StateFlags\"\t\t\"4\"", "\"StateFlags\"\t\t\"x\"");
+        assert_eq!(
+            error(AppManifest::parse(&bad_flags)),
+            "\"StateFlags\" is not a number"
+        );
+        assert_eq!(
+            error(AppManifest::parse("\"AppState\" \"\"")),
+            "missing \"appid\""
+        );
+        assert_eq!(
+            error(AppManifest::parse(LIBRARY_FOLDERS)),
+            "missing \"AppState\""
+        );
+    }
+
+    #[test]
+    fn epic_item() {
+        assert_eq!(
+            EpicItem::parse(EPIC_ITEM).unwrap(),
+            EpicItem {
+                app_name: String::from(EPIC_APP_ENHANCED),
+                install_location: PathBuf::from(r"C:\Program Files\Epic Games\GTAVEnhanced"),
+                app_version_string: String::from("1.0.889.22-CL1234567"),
+                incomplete: false,
+            }
+        );
+        let minimal = EpicItem::parse(r#"{"AppName": "a", "InstallLocation": "D:\\GTA"}"#).unwrap();
+        assert_eq!(minimal.app_version_string, "");
+        assert!(!minimal.incomplete);
+        assert_eq!(minimal.install_location, PathBuf::from(r"D:\GTA"));
+        assert!(EpicItem::parse(r#"{"AppName": "a"}"#).is_err());
+        assert!(EpicItem::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fmt,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const RT_VERSION: u32 = 16;
+const DIRECTORY_RESOURCE: usize = 2;
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF_04BD;
+/// resource sections of big exes are mostly icons, but there's no reason for one this big
+const MAX_RESOURCES_LEN: u32 = 64 * 1024 * 1024;
+
+/// the version of an exe, as shown in its properties in explorer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub file_version: Option<String>,
+    pub product_version: Option<String>,
+}
+
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file_version, &self.product_version) {
+            (Some(version), _) | (None, Some(version)) => write!(f, "{version}"),
+            (None, None) => write!(f, "unknown"),
+        }
+    }
+}
+
+/// reads the version resource out of an exe, only reading the parts of the file it needs
+pub fn read(path: &Path) -> Result<VersionInfo> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut pe = Pe::read_headers(&mut file)?;
+    let (rva, len) = pe.resources;
+    if rva == 0 || len == 0 {
+        bail!("no resources");
+    }
+    let resources = pe.read_rva(rva, len.min(MAX_RESOURCES_LEN))?;
+    let (data_rva, data_len) = find_version(&resources).context("no version resource")?;
+    let data = pe.read_rva(data_rva, data_len)?;
+    parse_version_info(&data).context("malformed version resource")
+}
+
+/// just enough of a PE file to find things in it by their rva
+struct Pe<'a> {
+    file: &'a mut File,
+    /// (virtual address, virtual size, raw data offset, raw data size) of each section
+    sections: Vec<(u32, u32, u32, u32)>,
+    resources: (u32, u32),
+}
+
+impl<'a> Pe<'a> {
+    fn read_headers(file: &'a mut File) -> Result<Self> {
+        let dos = read_at(file, 0, 64)?;
+        if dos.get(..2) != Some(b"MZ") {
+            bail!("not an exe");
+        }
+        let pe_offset = u64::from(u32_at(&dos, 0x3C).context("no PE offset")?);
+        let header = read_at(file, pe_offset, 24)?;
+        if header.get(..4) != Some(b"PE\0\0") {
+            bail!("no PE header");
+        }
+        let section_count = usize::from(u16_at(&header, 6).context("no section count")?);
+        let optional_len = u16_at(&header, 20).context("no optional header size")?;
+        let optional = read_at(file, pe_offset + 24, usize::from(optional_len))?;
+        // the data directories start at a different offset for 32 and 64 bit exes
+        let directories = match u16_at(&optional, 0) {
+            Some(0x10B) => 96,
+            Some(0x20B) => 112,
+            _ => bail!("unknown optional header"),
+        };
+        let directory = directories + DIRECTORY_RESOURCE * 8;
+        let resources = (
+            u32_at(&optional, directory).unwrap_or(0),
+            u32_at(&optional, directory + 4).unwrap_or(0),
+        );
+        let table = read_at(
+            file,
+            pe_offset + 24 + u64::from(optional_len),
+            section_count * 40,
+        )?;
+        let sections = table
+            .chunks_exact(40)
+            .filter_map(|section| {
+                Some((
+                    u32_at(section, 12)?,
+                    u32_at(section, 8)?,
+                    u32_at(section, 20)?,
+                    u32_at(section, 16)?,
+                ))
+            })
+            .collect();
+        Ok(Self {
+            file,
+            sections,
+            resources,
+        })
+    }
+
+    /// reads `len` bytes from where `rva` ends up in the file
+    fn read_rva(&mut self, rva: u32, len: u32) -> Result<Vec<u8>> {
+        let &(address, _, raw_offset, raw_len) = self
+            .sections
+            .iter()
+            .find(|&&(address, virtual_len, _, raw_len)| {
+                rva >= address && rva - address < virtual_len.max(raw_len)
+            })
+            .context("address isn't in any section")?;
+        let offset = rva - address;
+        // anything past the raw data is zeroed in memory, and nothing we want lives there
+        let len = len.min(raw_len.saturating_sub(offset));
+        read_at(
+            self.file,
+            u64::from(raw_offset) + u64::from(offset),
+            len as usize,
+        )
+    }
+}
+
+/// walks the resource tree down to the first version resource, returning its data's rva and size
+fn find_version(resources: &[u8]) -> Option<(u32, u32)> {
+    let by_type = entries(resources, 0)?;
+    let (_, versions) = by_type
+        .into_iter()
+        .find(|&(id, _)| id == Some(RT_VERSION))?;
+    let mut offset = versions;
+    // the levels below type are name then language, and any of them will do
+    for _ in 0..2 {
+        let (_, next) = *entries(resources, subdirectory(offset)?)?.first()?;
+        offset = next;
+    }
+    if offset & 0x8000_0000 != 0 {
+        return None;
+    }
+    let entry = offset as usize;
+    Some((u32_at(resources, entry)?, u32_at(resources, entry + 4)?))
+}
+
+/// the (id, offset) of every entry in the directory at `offset`, with None ids for named entries
+fn entries(resources: &[u8], offset: usize) -> Option<Vec<(Option<u32>, u32)>> {
+    let named = u16_at(resources, offset + 12)?;
+    let ids = u16_at(resources, offset + 14)?;
+    (0..usize::from(named) + usize::from(ids))
+        .map(|i| {
+            let entry = offset + 16 + i * 8;
+            let name = u32_at(resources, entry)?;
+            let id = (name & 0x8000_0000 == 0).then_some(name);
+            Some((id, u32_at(resources, entry + 4)?))
+        })
+        .collect()
+}
+
+const fn subdirectory(offset: u32) -> Option<usize> {
+    if offset & 0x8000_0000 == 0 {
+        None
+    } else {
+        Some((offset & 0x7FFF_FFFF) as usize)
+    }
+}
+
+/// one node of a VS_VERSIONINFO tree
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    children: &'a [u8],
+    len: usize,
+}
+
+/// every node starts on a 4 byte boundary, which all of these offsets are relative to
+fn parse_block(data: &[u8]) -> Option<Block<'_>> {
+    let len = usize::from(u16_at(data, 0)?);
+    let value_len = usize::from(u16_at(data, 2)?);
+    let is_text = u16_at(data, 4)? == 1;
+    let data = data.get(..len)?;
+    let (key, mut offset) = utf16_at(data, 6);
+    offset = align(offset);
+    // text values are measured in characters rather than bytes
+    let value_end = (offset + if is_text { value_len * 2 } else { value_len }).min(len);
+    let value = data.get(offset..value_end)?;
+    Some(Block {
+        key,
+        value,
+        children: data.get(align(value_end).min(len)..)?,
+        len,
+    })
+}
+
+fn children(data: &[u8]) -> impl Iterator<Item = Block<'_>> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let block = parse_block(data.get(offset..)?)?;
+        if block.len == 0 {
+            return None;
+        }
+        offset += align(block.len);
+        Some(block)
+    })
+}
+
+/// prefers the version strings, falling back on the fixed numbers every version resource has
+fn parse_version_info(data: &[u8]) -> Option<VersionInfo> {
+    let root = parse_block(data)?;
+    if root.key != "VS_VERSION_INFO" {
+        return None;
+    }
+    let mut info = VersionInfo::default();
+    let strings = children(root.children)
+        .filter(|block| block.key == "StringFileInfo")
+        .flat_map(|block| children(block.children))
+        .flat_map(|table| children(table.children));
+    for string in strings {
+        let (value, _) = utf16_at(string.value, 0);
+        let value = Some(value.trim().to_owned()).filter(|value| !value.is_empty());
+        match string.key.as_str() {
+            "FileVersion" => info.file_version = info.file_version.or(value),
+            "ProductVersion" => info.product_version = info.product_version.or(value),
+            _ => {}
+        }
+    }
+    let fixed = root.value;
+    if u32_at(fixed, 0) == Some(FIXED_FILE_INFO_SIGNATURE) {
+        let version = |offset| {
+            let (ms, ls) = (u32_at(fixed, offset)?, u32_at(fixed, offset + 4)?);
+            Some(format!(
+                "{}.{}.{}.{}",
+                ms >> 16,
+                ms & 0xFFFF,
+                ls >> 16,
+                ls & 0xFFFF
+            ))
+        };
+        info.file_version = info.file_version.or_else(|| version(8));
+        info.product_version = info.product_version.or_else(|| version(16));
+    }
+    Some(info)
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buffer = vec![0; len];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+const fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// a nul terminated utf-16 string, and the offset just past its terminator
+fn utf16_at(data: &[u8], offset: usize) -> (String, usize) {
+    let mut units = Vec::new();
+    let mut end = offset;
+    loop {
+        let unit = u16_at(data, end);
+        end += 2;
+        match unit {
+            // some strings run right up to the end of their value without a terminator
+            Some(0) | None => break,
+            Some(unit) => units.push(unit),
+        }
+    }
+    (String::from_utf16_lossy(&units), end.min(data.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// the fixtures are minimal exes which do nothing but return, with resources from llvm-rc
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/pe")
+            .join(name)
+    }
+
+    fn version(file_version: &str, product_version: &str) -> VersionInfo {
+        VersionInfo {
+            file_version: Some(file_version.to_owned()),
+            product_version: Some(product_version.to_owned()),
+        }
+    }
+
+    #[test]
+    fn version_strings() {
+        // the strings win over the fixed 1.2.0.0 product version
+        for name in ["version32.exe", "version64.exe"] {
+            assert_eq!(
+                read(&fixture(name)).unwrap(),
+                version("1.2.3.4", "1.2 beta"),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn fixed_file_info_fallback() {
+        let info = read(&fixture("fixed_only64.exe")).unwrap();
+        assert_eq!(info, version("5.6.7.8", "5.6.0.0"));
+        assert_eq!(info.to_string(), "5.6.7.8");
+    }
+
+    #[test]
+    fn unreadable() {
+        let error = |name| read(&fixture(name)).unwrap_err().to_string();
+        assert_eq!(error("no_resources32.exe"), "no resources");
+        assert_eq!(error("garbage.exe"), "not an exe");
+        // cut off part way through its resources
+        assert!(read(&fixture("truncated64.exe")).is_err());
+        assert!(read(&fixture("missing.exe")).is_err());
+        assert_eq!(VersionInfo::default().to_string(), "unknown");
+    }
+}
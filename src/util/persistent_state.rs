@@ -1,42 +1,530 @@
 use crate::{
-    features::launch::Platform,
-    gui::{app, settings::Settings},
+    features::launch::Profile,
+    gui::{
+        app,
+        settings::{LaunchVersion, Settings},
+    },
     util::consts::path,
 };
-use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::{self, File},
     io::Write,
+    time::{Duration, Instant},
 };
 
+/// bumped whenever the layout changes in a way that needs a migration
+pub const CONFIG_VERSION: u64 = 1;
+/// how many configs which loaded fine are kept around
+const CONFIG_BACKUPS: usize = 3;
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// how long to wait after the first failed save before trying again, doubling each time
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// how long the state has to stay the same before it's saved, so dragging a slider is one write
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct PersistentState {
-    pub launcher: Platform,
+    pub version: u64,
+    pub profiles: Vec<Profile>,
+    pub selected_profile: usize,
+    /// the version of each of the game's exes when it last ran
+    pub last_builds: BTreeMap<LaunchVersion, String>,
     pub anti_afk_enabled: bool,
     pub settings: Settings,
+    #[serde(skip)]
+    pub issue: Option<ConfigIssue>,
+    /// the config as it was read, so that it's only written again once something changes
+    #[serde(skip)]
+    pub loaded: Option<String>,
+}
+
+impl Default for PersistentState {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            profiles: Vec::new(),
+            selected_profile: 0,
+            last_builds: BTreeMap::new(),
+            anti_afk_enabled: false,
+            settings: Settings::default(),
+            issue: None,
+            loaded: None,
+        }
+    }
+}
+
+/// what went wrong loading the config, so that the user can be told about it
+#[derive(Clone, Debug)]
+pub enum ConfigIssue {
+    /// it wasn't valid json at all, so nothing could be loaded
+    Unreadable,
+    /// these keys had values we couldn't make sense of, so they were reset to their defaults
+    Reset(Vec<String>),
+    /// it was written by a newer version, so whatever this one doesn't know about will be dropped
+    Newer(u64),
+    /// writing it failed, so changes since the last save would be lost
+    SaveFailed(String),
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let backup = path::APP_CONFIG_UNREADABLE
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        match self {
+            Self::Unreadable => write!(
+                f,
+                "The config couldn't be read, so every setting was reset. \
+                The old config was kept as {backup}."
+            ),
+            Self::Reset(keys) => write!(
+                f,
+                "Some settings couldn't be read and were reset: {}. \
+                The old config was kept as {backup}.",
+                keys.join(", ")
+            ),
+            Self::Newer(version) => write!(
+                f,
+                "The config is from a newer version of GTA Tools, so some settings may be lost. \
+                The old config was kept as {}.",
+                path::app_config_newer(*version)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+            ),
+            Self::SaveFailed(why) => write!(f, "Settings couldn't be saved: {why}."),
+        }
+    }
 }
 
 impl PersistentState {
+    /// loads whatever it can from the config, migrating it from older layouts,
+    /// and backing it up if anything in it had to be thrown away
     pub fn get() -> Option<Self> {
-        fs::read_to_string(path::APP_CONFIG.as_path())
-            .ok()
-            .and_then(|config| serde_json::from_str::<Self>(&config).ok())
+        let config = fs::read_to_string(path::APP_CONFIG.as_path()).ok()?;
+        let (mut state, issue) = match serde_json::from_str::<Value>(&config) {
+            Ok(mut value) if value.is_object() => {
+                let version = version_of(&value);
+                migrate(&mut value);
+                let mut reset = Vec::new();
+                let state = repair::<Self>(&value, &mut reset);
+                let issue = if !reset.is_empty() {
+                    Some(ConfigIssue::Reset(reset))
+                } else {
+                    (version > CONFIG_VERSION).then_some(ConfigIssue::Newer(version))
+                };
+                (state, issue)
+            }
+            Ok(_) => {
+                log::error!("Couldn't parse the config: it isn't an object");
+                (Self::default(), Some(ConfigIssue::Unreadable))
+            }
+            Err(why) => {
+                log::error!("Couldn't parse the config: {why}");
+                (Self::default(), Some(ConfigIssue::Unreadable))
+            }
+        };
+        if let Some(issue) = &issue {
+            log::warn!("{issue}");
+            let backup = match issue {
+                ConfigIssue::Newer(version) => path::app_config_newer(*version),
+                _ => path::APP_CONFIG_UNREADABLE.clone(),
+            };
+            if let Err(why) = fs::copy(path::APP_CONFIG.as_path(), backup) {
+                log::error!("Couldn't back up the config: {why}");
+            }
+        } else if let Err(why) = rotate_backups(&config) {
+            log::error!("Couldn't back up the config: {why:#}");
+        }
+        state.issue = issue;
+        state.loaded = Some(config);
+        Some(state)
+    }
+
+    /// writes to a temporary file first, so that a failed write never leaves a truncated config
+    pub fn write(json: &str) -> Result<()> {
+        let temp = path::APP_CONFIG_TEMP.as_path();
+        let mut config_file = File::create(temp).context("creating the temporary config")?;
+        config_file
+            .write_all(json.as_bytes())
+            .and_then(|()| config_file.sync_all())
+            .context("writing the temporary config")?;
+        drop(config_file);
+        fs::rename(temp, path::APP_CONFIG.as_path()).context("replacing the config")?;
+        Ok(())
     }
 
-    pub fn set(&self) {
-        let mut config_file = File::create(path::APP_CONFIG.as_path()).unwrap();
-        let json = serde_json::to_string_pretty(&self).unwrap();
-        config_file.write_all(json.as_bytes()).unwrap();
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
     }
 
     pub fn apply_to(self, app: &mut app::App) {
         let Self {
-            launcher,
+            version: _,
+            profiles,
+            selected_profile,
+            last_builds,
             anti_afk_enabled,
             settings,
+            issue,
+            loaded,
         } = self;
-        app.launch.selected = launcher;
+        app.launch.profiles = profiles;
+        app.launch.selected = selected_profile;
+        app.game_build.last_seen = last_builds;
         app.anti_afk.enabled = anti_afk_enabled;
+        app.anti_afk.reschedule(&settings.anti_afk);
         app.settings = settings;
+        app.config_issue = issue;
+        if let Some(loaded) = loaded {
+            app.auto_save.saved(loaded);
+        }
+    }
+}
+
+/// notices when the persistent state has changed, and says to save it once it's settled down
+#[derive(Debug)]
+pub struct AutoSave {
+    saved: Option<String>,
+    pending: Option<(String, Instant)>,
+    last_check: Instant,
+    /// set after a failed save, so that a failing disk isn't retried every couple of seconds
+    retry_at: Option<Instant>,
+    retry_interval: Duration,
+}
+
+impl Default for AutoSave {
+    fn default() -> Self {
+        Self {
+            saved: None,
+            pending: None,
+            last_check: Instant::now(),
+            retry_at: None,
+            retry_interval: RETRY_INTERVAL,
+        }
+    }
+}
+
+impl AutoSave {
+    pub fn is_check_due(&self) -> bool {
+        self.last_check.elapsed() >= CHECK_INTERVAL
+            && self
+                .retry_at
+                .is_none_or(|retry_at| Instant::now() >= retry_at)
+    }
+
+    /// given the state as it is now, returns true once it's been different from what was saved
+    /// and stayed the same for long enough
+    pub fn should_save(&mut self, json: String) -> bool {
+        self.last_check = Instant::now();
+        if self.saved.as_ref() == Some(&json) {
+            self.pending = None;
+            return false;
+        }
+        match &self.pending {
+            Some((pending, since)) if *pending == json => since.elapsed() >= DEBOUNCE,
+            _ => {
+                self.pending = Some((json, Instant::now()));
+                false
+            }
+        }
+    }
+
+    /// this state is what's on disk now, so there's no need to save it again
+    pub fn saved(&mut self, json: String) {
+        self.saved = Some(json);
+        self.pending = None;
+        self.retry_at = None;
+        self.retry_interval = RETRY_INTERVAL;
+    }
+
+    /// holds off on saving again for a while, backing off further each time it fails
+    pub fn failed(&mut self) {
+        self.retry_at = Some(Instant::now() + self.retry_interval);
+        self.retry_interval = (self.retry_interval * 2).min(MAX_RETRY_INTERVAL);
+    }
+}
+
+/// shifts the backups along by one to make room for `config`, unless it's already the newest
+fn rotate_backups(config: &str) -> Result<()> {
+    let newest = path::app_config_backup(1);
+    if fs::read_to_string(&newest).is_ok_and(|backup| backup == config) {
+        return Ok(());
+    }
+    for n in (1..CONFIG_BACKUPS).rev() {
+        let backup = path::app_config_backup(n);
+        if backup.exists() {
+            fs::rename(&backup, path::app_config_backup(n + 1))
+                .with_context(|| format!("moving {}", backup.display()))?;
+        }
+    }
+    fs::write(&newest, config).with_context(|| format!("writing {}", newest.display()))?;
+    Ok(())
+}
+
+/// configs from before versioning count as version 0
+fn version_of(config: &Value) -> u64 {
+    config.get("version").and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// brings a config from any older layout up to the current one, a version at a time
+fn migrate(config: &mut Value) {
+    let version = version_of(config);
+    if version > CONFIG_VERSION {
+        log::warn!("The config is from a newer version of GTA Tools, some of it may be ignored");
+        return;
+    }
+    if version < 1 {
+        into_launch_profiles(config);
+    }
+    if let Some(config) = config.as_object_mut() {
+        config.insert("version".to_owned(), CONFIG_VERSION.into());
+    }
+}
+
+/// version 0 had a single launcher, with the game version and launch arguments in the settings
+fn into_launch_profiles(config: &mut Value) {
+    let Some(config) = config.as_object_mut() else {
+        return;
+    };
+    let launcher = config.remove("launcher");
+    let (version, args) = config
+        .get_mut("settings")
+        .and_then(Value::as_object_mut)
+        .map(|settings| {
+            (
+                settings.remove("launch_version"),
+                settings.remove("launch_args"),
+            )
+        })
+        .unwrap_or_default();
+    let has_profiles = config
+        .get("profiles")
+        .and_then(Value::as_array)
+        .is_some_and(|profiles| !profiles.is_empty());
+    if has_profiles || (launcher.is_none() && version.is_none() && args.is_none()) {
+        return;
+    }
+    let profile = [("platform", launcher), ("version", version), ("args", args)]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_owned(), value?)))
+        .collect::<serde_json::Map<_, _>>();
+    config.insert("profiles".to_owned(), vec![Value::Object(profile)].into());
+}
+
+/// deserializes as much of `config` as it can, resetting whatever doesn't fit to its default,
+/// and noting down the path of every key that was reset
+fn repair<T: Default + Serialize + DeserializeOwned>(config: &Value, reset: &mut Vec<String>) -> T {
+    if let Ok(state) = T::deserialize(config) {
+        return state;
+    }
+    let fits = |candidate: &Value| T::deserialize(candidate).is_ok();
+    let mut repaired = serde_json::to_value(T::default()).unwrap_or_default();
+    merge(&mut repaired, config, "", "", &fits, reset);
+    serde_json::from_value(repaired).unwrap_or_default()
+}
+
+/// copies each key or element of `config` across to the object or array at `pointer`
+/// for as long as the result still fits, going into those which don't fit as a whole to find
+/// the parts which are actually wrong. `path` is where `config` is, for noting down what was reset
+fn merge(
+    repaired: &mut Value,
+    config: &Value,
+    pointer: &str,
+    path: &str,
+    fits: &impl Fn(&Value) -> bool,
+    reset: &mut Vec<String>,
+) {
+    let entries: Vec<(String, &Value)> = match config {
+        Value::Object(entries) => entries
+            .iter()
+            .map(|(key, value)| (key.clone(), value))
+            .collect(),
+        // arrays are rebuilt an element at a time, so one bad element doesn't lose the rest
+        Value::Array(items) => match repaired.pointer_mut(pointer) {
+            Some(Value::Array(slot)) => {
+                slot.clear();
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (i.to_string(), value))
+                    .collect()
+            }
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+    for (key, value) in entries {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        // elements that were dropped shift the later ones down, so their index may differ
+        let index = match repaired.pointer(pointer) {
+            Some(Value::Array(items)) => items.len().to_string(),
+            _ => key.replace('~', "~0").replace('/', "~1"),
+        };
+        let child = format!("{pointer}/{index}");
+        if try_set(repaired, pointer, &key, value.clone(), fits) {
+            continue;
+        }
+        // it doesn't fit as a whole, so keep what does, starting from its default if it has one
+        let has_default = matches!(
+            (value, repaired.pointer(&child)),
+            (Value::Object(_), Some(Value::Object(_))) | (Value::Array(_), Some(Value::Array(_)))
+        );
+        let empty = match value {
+            Value::Object(_) => Some(Value::Object(serde_json::Map::new())),
+            Value::Array(_) => Some(Value::Array(Vec::new())),
+            _ => None,
+        };
+        if has_default || empty.is_some_and(|empty| try_set(repaired, pointer, &key, empty, fits)) {
+            merge(repaired, value, &child, &path, fits, reset);
+        } else {
+            reset.push(path);
+        }
+    }
+}
+
+/// sets `key` of the object at `pointer`, or adds an element to the array there,
+/// undoing it and returning false if the result doesn't fit
+fn try_set(
+    repaired: &mut Value,
+    pointer: &str,
+    key: &str,
+    value: Value,
+    fits: &impl Fn(&Value) -> bool,
+) -> bool {
+    let previous = match repaired.pointer_mut(pointer) {
+        Some(Value::Object(entries)) => entries.insert(key.to_owned(), value),
+        Some(Value::Array(items)) => {
+            items.push(value);
+            None
+        }
+        _ => return false,
+    };
+    if fits(repaired) {
+        return true;
+    }
+    match (repaired.pointer_mut(pointer), previous) {
+        (Some(Value::Object(entries)), Some(previous)) => {
+            entries.insert(key.to_owned(), previous);
+        }
+        (Some(Value::Object(entries)), None) => {
+            entries.remove(key);
+        }
+        (Some(Value::Array(items)), _) => {
+            items.pop();
+        }
+        _ => {}
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::launch::{Platform, PostLaunchAction};
+    use serde_json::json;
+
+    fn load(mut config: Value) -> (PersistentState, Vec<String>) {
+        migrate(&mut config);
+        let mut reset = Vec::new();
+        let state = repair(&config, &mut reset);
+        reset.sort();
+        (state, reset)
+    }
+
+    #[test]
+    fn single_launcher_becomes_a_profile() {
+        let mut config = json!({
+            "launcher": "Epic",
+            "settings": {
+                "start_elevated": true,
+                "launch_version": "Legacy",
+                "launch_args": { "extra": "-windowed" },
+            },
+        });
+        migrate(&mut config);
+        assert_eq!(config["version"], CONFIG_VERSION);
+        assert!(config.get("launcher").is_none());
+        assert!(config["settings"].get("launch_version").is_none());
+        assert!(config["settings"].get("launch_args").is_none());
+        let (state, reset) = load(config);
+        assert!(reset.is_empty());
+        assert_eq!(state.profiles.len(), 1);
+        assert_eq!(state.profiles[0].platform, Platform::Epic);
+        assert_eq!(state.profiles[0].version, LaunchVersion::Legacy);
+        assert_eq!(state.profiles[0].args.extra, "-windowed");
+        assert!(state.settings.start_elevated);
+    }
+
+    #[test]
+    fn existing_profiles_are_kept() {
+        let mut config = json!({
+            "launcher": "Epic",
+            "profiles": [{ "name": "Mine", "platform": "Rockstar" }],
+        });
+        migrate(&mut config);
+        assert_eq!(
+            config["profiles"],
+            json!([{ "name": "Mine", "platform": "Rockstar" }])
+        );
+        let mut empty = json!({ "version": 0 });
+        migrate(&mut empty);
+        assert_eq!(empty, json!({ "version": CONFIG_VERSION }));
+    }
+
+    #[test]
+    fn newer_configs_are_left_alone() {
+        let mut config = json!({ "version": CONFIG_VERSION + 1, "launcher": "Epic" });
+        let before = config.clone();
+        migrate(&mut config);
+        assert_eq!(config, before);
+    }
+
+    #[test]
+    fn only_bad_values_are_reset() {
+        let (state, reset) = load(json!({
+            "version": CONFIG_VERSION,
+            "anti_afk_enabled": "yes",
+            "profiles": [
+                { "name": "Epic", "platform": "Origin", "version": "Legacy" },
+                { "name": "Steam", "post_launch": ["EnableAntiAfk", "Dance"] },
+                5,
+                { "name": "Last", "pre_block": true },
+            ],
+            "settings": { "start_elevated": true, "theme": "Neon" },
+        }));
+        assert_eq!(
+            reset,
+            [
+                "anti_afk_enabled",
+                "profiles.0.platform",
+                "profiles.1.post_launch.1",
+                "profiles.2",
+                "settings.theme",
+            ]
+        );
+        let names = state.profiles.iter().map(|profile| profile.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), ["Epic", "Steam", "Last"]);
+        assert_eq!(state.profiles[0].platform, Platform::Steam);
+        assert_eq!(state.profiles[0].version, LaunchVersion::Legacy);
+        assert_eq!(
+            state.profiles[1].post_launch,
+            [PostLaunchAction::EnableAntiAfk]
+        );
+        assert!(state.profiles[2].pre_block);
+        assert!(!state.anti_afk_enabled);
+        assert!(state.settings.start_elevated);
     }
 }
@@ -0,0 +1,78 @@
+use crate::{gui::settings::Priority, util::system_info::ProcessHandle};
+use anyhow::{Context, Result};
+use windows::Win32::System::Threading::{
+    ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+    IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS, PROCESS_CREATION_FLAGS,
+};
+
+/// a single change made to a process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Priority(Priority),
+    Affinity(usize),
+}
+
+/// makes one change at a time to a process picked by its pid, standing between game tuning
+/// and the real processes so that its changes can be recorded instead of made
+pub trait ProcessControl: std::fmt::Debug {
+    fn apply(&mut self, pid: u32, change: Change) -> Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct Win32ProcessControl;
+
+impl ProcessControl for Win32ProcessControl {
+    fn apply(&mut self, pid: u32, change: Change) -> Result<()> {
+        let handle = ProcessHandle::tune(pid).context("opening the process")?;
+        match change {
+            Change::Priority(priority) => match priority_class(priority) {
+                Some(class) => handle
+                    .set_priority_class(class)
+                    .context("setting its priority"),
+                None => Ok(()),
+            },
+            Change::Affinity(mask) => handle
+                .set_affinity_mask(mask)
+                .context("setting its affinity"),
+        }
+    }
+}
+
+/// a fake backend which records the changes made to it instead of making them,
+/// refusing any listed in `fail`
+#[cfg(any(test, debug_assertions))]
+#[derive(Debug, Default)]
+pub struct RecordingProcessControl {
+    pub fail: Vec<Change>,
+    pub recorded: Vec<(u32, Change)>,
+}
+
+#[cfg(any(test, debug_assertions))]
+impl ProcessControl for RecordingProcessControl {
+    fn apply(&mut self, pid: u32, change: Change) -> Result<()> {
+        self.recorded.push((pid, change));
+        if self.fail.contains(&change) {
+            anyhow::bail!("access denied");
+        }
+        Ok(())
+    }
+}
+
+/// lets a test keep hold of a fake backend once it's been handed over
+#[cfg(test)]
+impl<T: ProcessControl> ProcessControl for std::rc::Rc<std::cell::RefCell<T>> {
+    fn apply(&mut self, pid: u32, change: Change) -> Result<()> {
+        self.borrow_mut().apply(pid, change)
+    }
+}
+
+const fn priority_class(priority: Priority) -> Option<PROCESS_CREATION_FLAGS> {
+    match priority {
+        Priority::Unchanged => None,
+        Priority::Idle => Some(IDLE_PRIORITY_CLASS),
+        Priority::BelowNormal => Some(BELOW_NORMAL_PRIORITY_CLASS),
+        Priority::Normal => Some(NORMAL_PRIORITY_CLASS),
+        Priority::AboveNormal => Some(ABOVE_NORMAL_PRIORITY_CLASS),
+        Priority::High => Some(HIGH_PRIORITY_CLASS),
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+use strum::Display;
+
+pub const CONFIG_DIR_ARG: &str = "--config-dir";
+pub const CONFIG_DIR_VAR: &str = "GTA_TOOLS_CONFIG_DIR";
+/// a file with this name next to our exe keeps all of our storage beside it
+const PORTABLE_MARKER: &str = "portable.txt";
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum Source {
+    #[strum(to_string = "set with --config-dir")]
+    Arg,
+    #[strum(to_string = "set with GTA_TOOLS_CONFIG_DIR")]
+    Var,
+    #[strum(to_string = "portable mode")]
+    Portable,
+    #[strum(to_string = "default")]
+    Default,
+}
+
+/// where our config, logs and playtime history live, and what decided that
+#[derive(Debug)]
+pub struct Location {
+    pub path: PathBuf,
+    pub source: Source,
+    /// what went wrong setting it up, such as `--config-dir` pointing somewhere we can't write
+    pub problem: Option<String>,
+}
+
+pub static LOCATION: LazyLock<Location> = LazyLock::new(locate);
+
+/// creates the requested location, falling back to the default one if that can't be done
+fn locate() -> Location {
+    let (path, source) = requested();
+    match fs::create_dir_all(&path) {
+        Ok(()) => Location {
+            path,
+            source,
+            problem: None,
+        },
+        Err(why) if source == Source::Default => Location {
+            problem: Some(format!("Couldn't create {}: {why}", path.display())),
+            path,
+            source,
+        },
+        Err(why) => {
+            let problem = format!(
+                "Couldn't use {} ({source}), so the default location is used instead: {why}",
+                path.display()
+            );
+            let path = default_dir();
+            // there's nowhere else to go if even this fails, and whatever writes there will say so
+            fs::create_dir_all(&path).ok();
+            Location {
+                path,
+                source: Source::Default,
+                problem: Some(problem),
+            }
+        }
+    }
+}
+
+/// the first of `--config-dir`, the environment variable, portable mode, then local app data
+fn requested() -> (PathBuf, Source) {
+    let arg = env::args_os()
+        .skip_while(|arg| arg.as_os_str() != CONFIG_DIR_ARG)
+        .nth(1)
+        .map(PathBuf::from);
+    let var = env::var_os(CONFIG_DIR_VAR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    let portable = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .filter(|dir| dir.join(PORTABLE_MARKER).is_file());
+    let (path, source) = if let Some(dir) = arg {
+        (dir, Source::Arg)
+    } else if let Some(dir) = var {
+        (dir, Source::Var)
+    } else if let Some(dir) = portable {
+        (dir, Source::Portable)
+    } else {
+        (default_dir(), Source::Default)
+    };
+    // an elevated instance of us starts in a different directory, so relative paths won't do
+    let path = std::path::absolute(&path).unwrap_or(path);
+    (path, source)
+}
+
+fn default_dir() -> PathBuf {
+    let local = env::var_os("LOCALAPPDATA").unwrap_or_else(|| ".".into());
+    PathBuf::from(local).join("GTA Tools")
+}
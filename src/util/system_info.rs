@@ -1,33 +1,57 @@
 use std::{
+    cell::OnceCell,
     ffi::{OsStr, OsString},
-    os::windows::{ffi::OsStringExt, process::CommandExt},
+    fmt,
+    os::windows::ffi::OsStringExt,
     path::{Path, PathBuf},
-    process::Command,
+    time::Duration,
 };
+use strum::Display;
 use windows::{
-    Win32::System::{
-        Diagnostics::ToolHelp::{
-            CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW,
-            TH32CS_SNAPPROCESS,
+    Win32::{
+        Foundation::{
+            CloseHandle, ERROR_ACCESS_DENIED, ERROR_INVALID_PARAMETER, FILETIME, HANDLE, HWND,
+            LPARAM, STILL_ACTIVE, WAIT_OBJECT_0, WAIT_TIMEOUT, WPARAM,
         },
-        Threading::{
-            CREATE_NO_WINDOW, OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
-            QueryFullProcessImageNameW,
+        System::{
+            Diagnostics::ToolHelp::{
+                CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW,
+                TH32CS_SNAPPROCESS,
+            },
+            Threading::{
+                GetExitCodeProcess, GetProcessTimes, OpenProcess, PROCESS_CREATION_FLAGS,
+                PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SET_INFORMATION,
+                PROCESS_SYNCHRONIZE, PROCESS_TERMINATE, QueryFullProcessImageNameW,
+                SetPriorityClass, SetProcessAffinityMask, TerminateProcess, WaitForSingleObject,
+            },
         },
+        UI::WindowsAndMessaging::{EnumWindows, GetWindowThreadProcessId, PostMessageW, WM_CLOSE},
     },
-    core::PWSTR,
+    core::{BOOL, PWSTR},
 };
 
-use crate::util::consts::game::{EXE_ENHANCED, EXE_LEGACY};
-
 #[derive(Clone, Debug)]
 pub struct Process {
     pid: u32,
+    parent_pid: u32,
     name: OsString,
-    exe: Option<PathBuf>,
+    /// only looked up when asked for, as it means opening the process
+    exe: OnceCell<Option<PathBuf>>,
+    /// when the process started as a FILETIME, looked up lazily for the same reason
+    created: OnceCell<Option<u64>>,
 }
 
 impl Process {
+    fn from_entry(process_entry: &PROCESSENTRY32W) -> Self {
+        Self {
+            pid: process_entry.th32ProcessID,
+            parent_pid: process_entry.th32ParentProcessID,
+            name: wide_array_to_os_string(&process_entry.szExeFile),
+            exe: OnceCell::new(),
+            created: OnceCell::new(),
+        }
+    }
+
     pub const fn pid(&self) -> u32 {
         self.pid
     }
@@ -37,26 +61,195 @@ impl Process {
     }
 
     pub fn exe(&self) -> Option<&Path> {
-        self.exe.as_deref()
+        self.exe
+            .get_or_init(|| get_exe_full_path(self.pid))
+            .as_deref()
+    }
+
+    pub fn created(&self) -> Option<u64> {
+        *self.created.get_or_init(|| get_creation_time(self.pid))
+    }
+
+    /// pids get reused, so a process only counts as a child if it started after its parent did
+    pub fn is_child_of(&self, parent: &Self) -> bool {
+        self.parent_pid == parent.pid
+            && self
+                .created()
+                .zip(parent.created())
+                .is_some_and(|(child, parent)| child > parent)
+    }
+
+    /// whether the process' exe lives somewhere inside one of the given folders
+    pub fn is_inside(&self, dirs: &[PathBuf]) -> bool {
+        self.exe()
+            .is_some_and(|exe| dirs.iter().any(|dir| starts_with_ignore_case(exe, dir)))
+    }
+
+    /// opens a handle which only allows waiting on the process and reading its exit code
+    pub fn watch(&self) -> Result<ProcessHandle, KillError> {
+        let access = PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION;
+        unsafe { OpenProcess(access, false, self.pid) }
+            .map(ProcessHandle)
+            .map_err(|e| KillError::from_win32(&e))
+    }
+
+    /// tries each method in turn until the process has exited, waiting up to `timeout` for each
+    pub fn kill(&self, methods: &[KillMethod], timeout: Duration) -> Result<KillMethod, KillError> {
+        let handle = ProcessHandle::open(self.pid)?;
+        let mut result = Err(KillError::Timeout);
+        for &method in methods {
+            result = handle.ensure_running().and_then(|()| {
+                match method {
+                    KillMethod::Graceful => close_windows(self.pid),
+                    KillMethod::Terminate => unsafe { TerminateProcess(handle.0, 1) }
+                        .map_err(|e| KillError::from_win32(&e)),
+                }?;
+                handle.wait(timeout).map(|()| method)
+            });
+            if !matches!(result, Err(KillError::Timeout | KillError::NoWindow)) {
+                break;
+            }
+        }
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum KillMethod {
+    /// ask the process' windows to close
+    #[strum(to_string = "closed")]
+    Graceful,
+    #[strum(to_string = "terminated")]
+    Terminate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillError {
+    AccessDenied,
+    AlreadyExited,
+    NotFound,
+    Timeout,
+    /// the process has no windows to close gracefully
+    NoWindow,
+    Other(i32),
+}
+
+impl KillError {
+    fn from_win32(error: &windows::core::Error) -> Self {
+        if error.code() == ERROR_ACCESS_DENIED.to_hresult() {
+            Self::AccessDenied
+        } else if error.code() == ERROR_INVALID_PARAMETER.to_hresult() {
+            Self::NotFound
+        } else {
+            Self::Other(error.code().0)
+        }
+    }
+}
+
+impl fmt::Display for KillError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccessDenied => write!(f, "access denied"),
+            Self::AlreadyExited => write!(f, "already exited"),
+            Self::NotFound => write!(f, "not found"),
+            Self::Timeout => write!(f, "timed out"),
+            Self::NoWindow => write!(f, "no window to close"),
+            Self::Other(code) => write!(f, "failed with {code:#010x}"),
+        }
+    }
+}
+
+impl std::error::Error for KillError {}
+
+#[derive(Debug)]
+pub struct ProcessHandle(HANDLE);
+
+impl ProcessHandle {
+    fn open(pid: u32) -> Result<Self, KillError> {
+        let access = PROCESS_TERMINATE | PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION;
+        unsafe { OpenProcess(access, false, pid) }
+            .map(Self)
+            .map_err(|e| KillError::from_win32(&e))
+    }
+
+    /// opens a handle which allows changing the process' priority and affinity
+    pub fn tune(pid: u32) -> windows::core::Result<Self> {
+        let access = PROCESS_SET_INFORMATION | PROCESS_QUERY_LIMITED_INFORMATION;
+        unsafe { OpenProcess(access, false, pid) }.map(Self)
+    }
+
+    pub fn set_priority_class(&self, class: PROCESS_CREATION_FLAGS) -> windows::core::Result<()> {
+        unsafe { SetPriorityClass(self.0, class) }
+    }
+
+    pub fn set_affinity_mask(&self, mask: usize) -> windows::core::Result<()> {
+        unsafe { SetProcessAffinityMask(self.0, mask) }
+    }
+
+    /// the process's exit code, or None while it's still running
+    pub fn exit_code(&self) -> Option<u32> {
+        let mut exit_code = 0;
+        unsafe { GetExitCodeProcess(self.0, &raw mut exit_code) }.ok()?;
+        (exit_code != STILL_ACTIVE.0 as u32).then_some(exit_code)
+    }
+
+    fn ensure_running(&self) -> Result<(), KillError> {
+        let mut exit_code = 0;
+        unsafe { GetExitCodeProcess(self.0, &raw mut exit_code) }
+            .map_err(|e| KillError::from_win32(&e))?;
+        if exit_code == STILL_ACTIVE.0 as u32 {
+            Ok(())
+        } else {
+            Err(KillError::AlreadyExited)
+        }
     }
 
-    pub fn kill(&self) -> bool {
-        let mut taskkill = Command::new("taskkill.exe");
-        taskkill.creation_flags(CREATE_NO_WINDOW.0);
-        taskkill.arg("/F").arg("/PID").arg(self.pid.to_string());
-        match taskkill.output() {
-            Ok(output) => output.status.success(),
-            Err(_) => false,
+    fn wait(&self, timeout: Duration) -> Result<(), KillError> {
+        let timeout = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+        match unsafe { WaitForSingleObject(self.0, timeout) } {
+            WAIT_OBJECT_0 => Ok(()),
+            WAIT_TIMEOUT => Err(KillError::Timeout),
+            _ => Err(KillError::from_win32(&windows::core::Error::from_thread())),
         }
     }
 }
 
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) }.ok();
+    }
+}
+
+/// posts WM_CLOSE to every top level window belonging to the process
+fn close_windows(pid: u32) -> Result<(), KillError> {
+    unsafe extern "system" fn callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let (pid, found) = unsafe { &mut *(lparam.0 as *mut (u32, bool)) };
+        let mut window_pid = 0;
+        unsafe { GetWindowThreadProcessId(hwnd, Some(&raw mut window_pid)) };
+        if window_pid == *pid
+            && unsafe { PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0)) }.is_ok()
+        {
+            *found = true;
+        }
+        true.into()
+    }
+    let mut state = (pid, false);
+    unsafe { EnumWindows(Some(callback), LPARAM((&raw mut state) as isize)) }
+        .map_err(|e| KillError::from_win32(&e))?;
+    if state.1 {
+        Ok(())
+    } else {
+        Err(KillError::NoWindow)
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct SystemInfo {
     processes: Vec<Process>,
 }
 
 impl SystemInfo {
+    /// takes a snapshot of every process' name and parents, leaving exe paths until they're needed
     pub fn refresh(&mut self) {
         let mut processes = Vec::new();
         let snapshot_handle = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) }.unwrap();
@@ -65,50 +258,58 @@ impl SystemInfo {
             ..Default::default()
         };
         unsafe { Process32FirstW(snapshot_handle, &raw mut process_entry) }.unwrap();
-        let exe_full_path = get_exe_full_path(&process_entry);
-        processes.push(Process {
-            pid: process_entry.th32ProcessID,
-            name: wide_array_to_os_string(&process_entry.szExeFile),
-            exe: exe_full_path,
-        });
+        processes.push(Process::from_entry(&process_entry));
         while unsafe { Process32NextW(snapshot_handle, &raw mut process_entry) }.is_ok() {
-            let exe_full_path = get_exe_full_path(&process_entry);
-            processes.push(Process {
-                pid: process_entry.th32ProcessID,
-                name: wide_array_to_os_string(&process_entry.szExeFile),
-                exe: exe_full_path,
-            });
+            processes.push(Process::from_entry(&process_entry));
         }
+        unsafe { CloseHandle(snapshot_handle) }.ok();
         self.processes = processes;
     }
 
-    pub fn get_game_exe_path(&mut self) -> Option<&Path> {
-        self.refresh();
-        self.processes()
+    pub fn processes(&self) -> &[Process] {
+        &self.processes
+    }
+
+    /// processes matching any of the given exe names, optionally along with all their descendants
+    pub fn find_by_names(&self, names: &[&str], include_children: bool) -> Vec<&Process> {
+        let found = self
+            .processes
             .iter()
-            .find(|p| p.name() == EXE_ENHANCED || p.name() == EXE_LEGACY)
-            .and_then(|p| p.exe())
+            .filter(|p| names.iter().any(|name| p.name().eq_ignore_ascii_case(name)))
+            .collect::<Vec<_>>();
+        if include_children {
+            self.with_children(found)
+        } else {
+            found
+        }
     }
 
-    pub fn processes(&self) -> &[Process] {
-        &self.processes
+    /// the given processes followed by all of their descendants
+    pub fn with_children<'a>(&'a self, mut found: Vec<&'a Process>) -> Vec<&'a Process> {
+        let mut i = 0;
+        while i < found.len() {
+            let parent = found[i];
+            for child in self.processes.iter().filter(|p| p.is_child_of(parent)) {
+                if !found.iter().any(|p| p.pid() == child.pid()) {
+                    found.push(child);
+                }
+            }
+            i += 1;
+        }
+        found
     }
 }
 
-fn get_exe_full_path(process_entry: &PROCESSENTRY32W) -> Option<PathBuf> {
-    let process_handle_result = unsafe {
-        OpenProcess(
-            PROCESS_QUERY_LIMITED_INFORMATION,
-            false,
-            process_entry.th32ProcessID,
-        )
-    };
+fn get_exe_full_path(pid: u32) -> Option<PathBuf> {
+    let process_handle_result =
+        unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) };
     process_handle_result.map_or(None, |process_handle| {
+        let process_handle = ProcessHandle(process_handle);
         let mut exe_name = [0u16; 260];
         let mut dw_size = exe_name.len() as u32;
         let image_name_result = unsafe {
             QueryFullProcessImageNameW(
-                process_handle,
+                process_handle.0,
                 PROCESS_NAME_WIN32,
                 PWSTR(exe_name.as_mut_ptr()),
                 &raw mut dw_size,
@@ -121,6 +322,39 @@ fn get_exe_full_path(process_entry: &PROCESSENTRY32W) -> Option<PathBuf> {
     })
 }
 
+fn get_creation_time(pid: u32) -> Option<u64> {
+    let process_handle =
+        ProcessHandle(unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?);
+    let (mut created, mut exited, mut kernel, mut user) = (
+        FILETIME::default(),
+        FILETIME::default(),
+        FILETIME::default(),
+        FILETIME::default(),
+    );
+    unsafe {
+        GetProcessTimes(
+            process_handle.0,
+            &raw mut created,
+            &raw mut exited,
+            &raw mut kernel,
+            &raw mut user,
+        )
+    }
+    .ok()?;
+    Some((u64::from(created.dwHighDateTime) << 32) | u64::from(created.dwLowDateTime))
+}
+
+/// windows paths aren't case sensitive, which `Path::starts_with` is
+fn starts_with_ignore_case(path: &Path, prefix: &Path) -> bool {
+    let mut components = path.components();
+    prefix.components().next().is_some()
+        && prefix.components().all(|prefix| {
+            components
+                .next()
+                .is_some_and(|component| component.as_os_str().eq_ignore_ascii_case(prefix))
+        })
+}
+
 fn wide_array_to_os_string(wide: &[u16]) -> OsString {
     let null_pos = wide.iter().position(|&x| x == 0).unwrap_or(wide.len());
     OsString::from_wide(&wide[..null_pos])
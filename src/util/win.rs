@@ -1,20 +1,36 @@
+use crate::util::storage;
+use std::time::Duration;
 use windows::{
     Win32::{
-        Foundation::{CloseHandle, HANDLE},
+        Foundation::{CloseHandle, FILETIME, HANDLE, SYSTEMTIME},
         Security::{GetTokenInformation, TOKEN_ELEVATION, TOKEN_QUERY, TokenElevation},
-        System::Threading::{GetCurrentProcess, OpenProcessToken},
+        System::{
+            Power::{ES_CONTINUOUS, ES_SYSTEM_REQUIRED, SetSuspendState, SetThreadExecutionState},
+            SystemInformation::{GetLocalTime, GetTickCount},
+            Threading::{GetCurrentProcess, OpenProcessToken},
+            Time::{
+                FileTimeToSystemTime, GetTimeZoneInformation, SystemTimeToFileTime,
+                SystemTimeToTzSpecificLocalTime, TIME_ZONE_INFORMATION,
+            },
+        },
         UI::{
-            Input::KeyboardAndMouse::{GetAsyncKeyState, VIRTUAL_KEY},
+            Input::KeyboardAndMouse::{
+                GetAsyncKeyState, GetLastInputInfo, LASTINPUTINFO, VIRTUAL_KEY,
+            },
             Shell::ShellExecuteW,
             WindowsAndMessaging::{
-                CURSOR_SHOWING, CURSORINFO, GetCursorInfo, GetForegroundWindow, GetWindowTextW,
-                SW_NORMAL,
+                CURSOR_SHOWING, CURSORINFO, FindWindowW, GetCursorInfo, GetForegroundWindow,
+                GetWindowTextW, IsHungAppWindow, SW_NORMAL,
             },
         },
     },
     core::{HSTRING, PCWSTR},
 };
 
+const FILETIME_TICKS_PER_SEC: u64 = 10_000_000;
+/// seconds from the FILETIME epoch in 1601 to the unix one
+const FILETIME_UNIX_EPOCH: i64 = 11_644_473_600;
+
 pub enum ElevationExitMethod<'a> {
     Gentle(&'a mut bool),
     Forced,
@@ -37,19 +53,122 @@ pub fn is_window_focused(target_title: &str) -> bool {
     current_title == target_title
 }
 
+/// whether a top level window with exactly this title exists, focused or not
+pub fn is_window_present(target_title: &str) -> bool {
+    unsafe { FindWindowW(PCWSTR::null(), &HSTRING::from(target_title)) }.is_ok()
+}
+
+/// whether the window with exactly this title is responding, or None if there's no such window
+pub fn is_window_responding(target_title: &str) -> Option<bool> {
+    let hwnd = unsafe { FindWindowW(PCWSTR::null(), &HSTRING::from(target_title)) }.ok()?;
+    Some(!unsafe { IsHungAppWindow(hwnd) }.as_bool())
+}
+
 pub fn is_any_key_pressed(keys: &[VIRTUAL_KEY]) -> bool {
     keys.iter()
         .any(|&key| unsafe { GetAsyncKeyState(i32::from(key.0)) } & i16::MIN != 0)
 }
 
+/// how long it has been since the last keyboard or mouse input anywhere on the system
+pub fn idle_time() -> Duration {
+    let mut lii = LASTINPUTINFO {
+        cbSize: u32::try_from(std::mem::size_of::<LASTINPUTINFO>()).unwrap(),
+        ..Default::default()
+    };
+    if !unsafe { GetLastInputInfo(&raw mut lii) }.as_bool() {
+        return Duration::ZERO;
+    }
+    let ticks = unsafe { GetTickCount() }.wrapping_sub(lii.dwTime);
+    Duration::from_millis(u64::from(ticks))
+}
+
+/// the current local wall clock time, as (hour, minute, second)
+pub fn local_time() -> (u16, u16, u16) {
+    let time = unsafe { GetLocalTime() };
+    (time.wHour, time.wMinute, time.wSecond)
+}
+
+/// how many seconds local time is currently ahead of UTC
+pub fn utc_offset() -> i64 {
+    let mut tzi = TIME_ZONE_INFORMATION::default();
+    // the return value says which of the biases is in effect right now
+    let bias = match unsafe { GetTimeZoneInformation(&raw mut tzi) } {
+        1 => tzi.Bias + tzi.StandardBias,
+        2 => tzi.Bias + tzi.DaylightBias,
+        _ => tzi.Bias,
+    };
+    -i64::from(bias) * 60
+}
+
+/// how many seconds local time was ahead of UTC at the given unix time, which unlike
+/// [`utc_offset`] accounts for daylight saving having been different back then
+pub fn utc_offset_at(unix_secs: i64) -> i64 {
+    to_local_secs(unix_secs).map_or_else(|_| utc_offset(), |local| local - unix_secs)
+}
+
+/// windows only applies the daylight saving rules of a given date when converting SYSTEMTIMEs
+fn to_local_secs(unix_secs: i64) -> windows::core::Result<i64> {
+    let secs = u64::try_from(unix_secs.saturating_add(FILETIME_UNIX_EPOCH)).unwrap_or_default();
+    let ticks = secs.saturating_mul(FILETIME_TICKS_PER_SEC);
+    let filetime = FILETIME {
+        dwLowDateTime: ticks as u32,
+        dwHighDateTime: (ticks >> 32) as u32,
+    };
+    let (mut utc, mut local) = (SYSTEMTIME::default(), SYSTEMTIME::default());
+    let mut local_filetime = FILETIME::default();
+    unsafe {
+        FileTimeToSystemTime(&raw const filetime, &raw mut utc)?;
+        SystemTimeToTzSpecificLocalTime(None, &raw const utc, &raw mut local)?;
+        SystemTimeToFileTime(&raw const local, &raw mut local_filetime)?;
+    }
+    let ticks =
+        (u64::from(local_filetime.dwHighDateTime) << 32) | u64::from(local_filetime.dwLowDateTime);
+    Ok(i64::try_from(ticks / FILETIME_TICKS_PER_SEC).unwrap_or(i64::MAX) - FILETIME_UNIX_EPOCH)
+}
+
+/// stops windows from going to sleep on its own while `awake` is true
+pub fn keep_awake(awake: bool) {
+    let flags = if awake {
+        ES_CONTINUOUS | ES_SYSTEM_REQUIRED
+    } else {
+        ES_CONTINUOUS
+    };
+    unsafe { SetThreadExecutionState(flags) };
+}
+
+pub fn sleep() {
+    if !unsafe { SetSuspendState(false, false, false) } {
+        log::error!("couldn't put the system to sleep");
+    }
+}
+
 pub fn elevate(closing: ElevationExitMethod) {
     let exe = std::env::current_exe().unwrap();
+    // when elevating on startup, pass our arguments along so that things like launching
+    // a profile still happen. later on they've already been acted on, so they're dropped
+    let mut args = match closing {
+        ElevationExitMethod::Gentle(_) => Vec::new(),
+        ElevationExitMethod::Forced => std::env::args().skip(1).collect(),
+    };
+    // the elevated instance doesn't necessarily get our environment, so say where storage is
+    let location = &storage::LOCATION;
+    let overridden = matches!(location.source, storage::Source::Arg | storage::Source::Var);
+    if overridden && !args.iter().any(|arg| arg == storage::CONFIG_DIR_ARG) {
+        args.push(storage::CONFIG_DIR_ARG.to_owned());
+        args.push(location.path.to_string_lossy().into_owned());
+    }
+    let args = args
+        .iter()
+        .map(String::as_str)
+        .map(quote_arg)
+        .collect::<Vec<_>>()
+        .join(" ");
     unsafe {
         ShellExecuteW(
             None,
             &HSTRING::from("runas"),
             &HSTRING::from(exe.as_path()),
-            PCWSTR::null(),
+            &HSTRING::from(args),
             PCWSTR::null(),
             SW_NORMAL,
         );
@@ -60,6 +179,30 @@ pub fn elevate(closing: ElevationExitMethod) {
     }
 }
 
+/// quotes an argument so that it survives being split up again. backslashes only need escaping
+/// when they come before a quote, which includes the closing one
+fn quote_arg(arg: &str) -> String {
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        if c == '\\' {
+            backslashes += 1;
+            continue;
+        }
+        let escapes = if c == '"' {
+            backslashes * 2 + 1
+        } else {
+            backslashes
+        };
+        quoted.extend(std::iter::repeat_n('\\', escapes));
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
 pub fn is_elevated() -> bool {
     let mut token: HANDLE = HANDLE::default();
     if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &raw mut token) }.is_err() {
@@ -95,3 +238,18 @@ pub fn is_system_theme_light() -> bool {
     };
     apps_use_light_theme == 1
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_args() {
+        assert_eq!(quote_arg("plain"), r#""plain""#);
+        assert_eq!(quote_arg(""), r#""""#);
+        assert_eq!(quote_arg(r"C:\Some Dir\"), r#""C:\Some Dir\\""#);
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(quote_arg(r"a\\b"), r#""a\\b""#);
+    }
+}